@@ -492,7 +492,7 @@ async fn main() -> wry::Result<()> {
 
 async fn tray() -> Result<(), eframe::Error> {
     let path = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/icon.png");
-    let icon = tray_icon_load_icon(std::path::Path::new(path));
+    let icon = tray_icon::Icon::from_path(path, None).expect("Failed to open icon");
 
     // Since egui uses winit under the hood and doesn't use gtk on Linux, and we need gtk for
     // the tray icon to show up, we need to spawn a thread
@@ -638,15 +638,3 @@ fn muda_load_icon(path: &std::path::Path) -> muda::Icon {
     };
     muda::Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
 }
-
-fn tray_icon_load_icon(path: &std::path::Path) -> tray_icon::Icon {
-    let (icon_rgba, icon_width, icon_height) = {
-        let image = image::open(path)
-            .expect("Failed to open icon path")
-            .into_rgba8();
-        let (width, height) = image.dimensions();
-        let rgba = image.into_raw();
-        (rgba, width, height)
-    };
-    tray_icon::Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
-}
@@ -61,7 +61,7 @@ fn main() {
 
         match event {
             Event::NewEvents(tao::event::StartCause::Init) => {
-                let icon = load_icon(std::path::Path::new(path));
+                let icon = tray_icon::Icon::from_path(path, None).expect("Failed to open icon");
 
                 // We create the icon once the event loop is actually running
                 // to prevent issues like https://github.com/tauri-apps/tray-icon/issues/90
@@ -102,15 +102,3 @@ fn main() {
         }
     })
 }
-
-fn load_icon(path: &std::path::Path) -> tray_icon::Icon {
-    let (icon_rgba, icon_width, icon_height) = {
-        let image = image::open(path)
-            .expect("Failed to open icon path")
-            .into_rgba8();
-        let (width, height) = image.dimensions();
-        let rgba = image.into_raw();
-        (rgba, width, height)
-    };
-    tray_icon::Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
-}
@@ -24,10 +24,10 @@ fn main() {
     // the tray icon to show up, we need to spawn a thread
     // where we initialize gtk and create the tray_icon
     #[cfg(target_os = "linux")]
-    std::thread::spawn(|| {
+    std::thread::spawn(move || {
         use tray_icon::menu::Menu;
 
-        let icon = load_icon(std::path::Path::new(path));
+        let icon = tray_icon::Icon::from_path(path, None).expect("Failed to open icon");
 
         gtk::init().unwrap();
         let _tray_icon = TrayIconBuilder::new()
@@ -61,7 +61,7 @@ fn main() {
         match event {
             #[cfg(not(target_os = "linux"))]
             Event::NewEvents(winit::event::StartCause::Init) => {
-                let icon = load_icon(std::path::Path::new(path));
+                let icon = tray_icon::Icon::from_path(path, None).expect("Failed to open icon");
 
                 // We create the icon once the event loop is actually running
                 // to prevent issues like https://github.com/tauri-apps/tray-icon/issues/90
@@ -93,15 +93,3 @@ fn main() {
         }
     });
 }
-
-fn load_icon(path: &std::path::Path) -> tray_icon::Icon {
-    let (icon_rgba, icon_width, icon_height) = {
-        let image = image::open(path)
-            .expect("Failed to open icon path")
-            .into_rgba8();
-        let (width, height) = image.dimensions();
-        let rgba = image.into_raw();
-        (rgba, width, height)
-    };
-    tray_icon::Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
-}
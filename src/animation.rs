@@ -0,0 +1,422 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::time::Duration;
+
+use crate::{
+    icon::{blend_over, BadIcon, Icon, RgbaIcon, PIXEL_SIZE},
+    Error,
+};
+
+/// An animated icon, played with [`TrayIcon::set_animation`](crate::TrayIcon::set_animation).
+#[derive(Debug, Clone)]
+pub struct Animation {
+    /// The frames of the animation, in order.
+    pub frames: Vec<Icon>,
+    /// How long each frame is shown, there must be one duration per frame and none can be zero.
+    pub durations: Vec<Duration>,
+    /// How many times the animation is played.
+    pub repeat: Repeat,
+}
+
+/// How many times an [`Animation`] is played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Repeat {
+    /// The animation loops until it's stopped.
+    #[default]
+    Infinite,
+    /// The animation is played this many times, at least once, and then stays on its last frame.
+    Count(u32),
+}
+
+impl Animation {
+    /// Loads an animated PNG, composing its frames as they are displayed.
+    ///
+    /// The default image of the file is skipped when it isn't part of the animation.
+    /// Like in browsers, frames without a delay or a delay of 10ms are shown for 100ms.
+    pub fn from_apng(bytes: &[u8]) -> Result<Self, BadIcon> {
+        let decoding_error = |e: png::DecodingError| BadIcon::DecodingError {
+            format: "APNG",
+            reason: e.to_string(),
+        };
+
+        let mut decoder = png::Decoder::new(bytes);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(decoding_error)?;
+
+        let info = reader.info();
+        let control = info
+            .animation_control
+            .ok_or_else(|| BadIcon::DecodingError {
+                format: "APNG",
+                reason: "the image isn't animated".to_string(),
+            })?;
+        let mut canvas = Canvas::new(info.width, info.height);
+        let mut buf = vec![0; reader.output_buffer_size()];
+        if info.frame_control.is_none() {
+            reader.next_frame(&mut buf).map_err(decoding_error)?;
+        }
+
+        let mut animation = Animation {
+            frames: Vec::new(),
+            durations: Vec::new(),
+            repeat: match control.num_plays {
+                0 => Repeat::Infinite,
+                plays => Repeat::Count(plays),
+            },
+        };
+        for i in 0..control.num_frames {
+            let output = reader.next_frame(&mut buf).map_err(decoding_error)?;
+            let image = RgbaIcon::from_png_frame(&buf[..output.buffer_size()], &output)?;
+            let frame = reader
+                .info()
+                .frame_control
+                .expect("APNG frames have a fcTL chunk");
+
+            let dispose = match frame.dispose_op {
+                png::DisposeOp::None => Dispose::Keep,
+                png::DisposeOp::Background => Dispose::Background,
+                // there's nothing to go back to before the first frame
+                png::DisposeOp::Previous if i == 0 => Dispose::Background,
+                png::DisposeOp::Previous => Dispose::Previous,
+            };
+            let icon = canvas.draw(
+                &image,
+                (frame.x_offset, frame.y_offset),
+                frame.blend_op == png::BlendOp::Over,
+                dispose,
+            )?;
+            let denominator = match frame.delay_den {
+                0 => 100,
+                den => den as u64,
+            };
+            // same as GIFs, otherwise frames without a delay would be rescheduled right away
+            let delay = match frame.delay_num as u64 * 1000 / denominator {
+                0..=10 => 100,
+                delay => delay,
+            };
+            animation.frames.push(icon);
+            animation.durations.push(Duration::from_millis(delay));
+        }
+
+        if animation.frames.is_empty() {
+            return Err(BadIcon::NoImages);
+        }
+        Ok(animation)
+    }
+
+    /// Loads an animated GIF, composing its frames as they are displayed.
+    ///
+    /// Like in browsers, frames without a delay or a delay of 10ms are shown for 100ms.
+    #[cfg(feature = "gif")]
+    pub fn from_gif(bytes: &[u8]) -> Result<Self, BadIcon> {
+        let decoding_error = |e: gif::DecodingError| BadIcon::DecodingError {
+            format: "GIF",
+            reason: e.to_string(),
+        };
+
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(bytes).map_err(decoding_error)?;
+        let mut canvas = Canvas::new(decoder.width() as u32, decoder.height() as u32);
+
+        let mut frames = Vec::new();
+        let mut durations = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().map_err(decoding_error)? {
+            let image = RgbaIcon::from_rgba(
+                frame.buffer.to_vec(),
+                frame.width as u32,
+                frame.height as u32,
+            )?;
+            let dispose = match frame.dispose {
+                gif::DisposalMethod::Any | gif::DisposalMethod::Keep => Dispose::Keep,
+                gif::DisposalMethod::Background => Dispose::Background,
+                gif::DisposalMethod::Previous => Dispose::Previous,
+            };
+            frames.push(canvas.draw(
+                &image,
+                (frame.left as u32, frame.top as u32),
+                true,
+                dispose,
+            )?);
+            // delays are in hundredths of a second
+            let delay = if frame.delay <= 1 { 10 } else { frame.delay };
+            durations.push(Duration::from_millis(delay as u64 * 10));
+        }
+
+        if frames.is_empty() {
+            return Err(BadIcon::NoImages);
+        }
+        Ok(Animation {
+            frames,
+            durations,
+            repeat: match decoder.repeat() {
+                gif::Repeat::Infinite => Repeat::Infinite,
+                // the count is the number of repetitions after the first play
+                gif::Repeat::Finite(count) => Repeat::Count(count as u32 + 1),
+            },
+        })
+    }
+}
+
+/// What happens to the area of a frame once its duration is over.
+enum Dispose {
+    Keep,
+    /// Clear the area to transparent.
+    Background,
+    /// Restore the area as it was before the frame.
+    Previous,
+}
+
+/// The image frames of animated files are drawn onto, as they only hold the changed area.
+struct Canvas(RgbaIcon);
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Self {
+        Canvas(RgbaIcon {
+            rgba: vec![0; width as usize * height as usize * PIXEL_SIZE],
+            width,
+            height,
+        })
+    }
+
+    /// Draws `image` at `(left, top)` and returns the resulting frame, then disposes of it.
+    fn draw(
+        &mut self,
+        image: &RgbaIcon,
+        (left, top): (u32, u32),
+        blend: bool,
+        dispose: Dispose,
+    ) -> Result<Icon, BadIcon> {
+        let previous = matches!(dispose, Dispose::Previous).then(|| self.0.rgba.clone());
+
+        // frames are clipped to the canvas
+        let width = image.width.min(self.0.width.saturating_sub(left)) as usize;
+        let height = image.height.min(self.0.height.saturating_sub(top)) as usize;
+        let offset = |x: usize, y: usize| {
+            ((top as usize + y) * self.0.width as usize + left as usize + x) * PIXEL_SIZE
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let src = (y * image.width as usize + x) * PIXEL_SIZE;
+                let src = &image.rgba[src..src + PIXEL_SIZE];
+                let dst = offset(x, y);
+                let dst = &mut self.0.rgba[dst..dst + PIXEL_SIZE];
+                if blend {
+                    blend_over(dst, src);
+                } else {
+                    dst.copy_from_slice(src);
+                }
+            }
+        }
+
+        let frame = Icon::from_rgba(self.0.rgba.clone(), self.0.width, self.0.height)?;
+
+        match (dispose, previous) {
+            (Dispose::Previous, Some(previous)) => self.0.rgba = previous,
+            (Dispose::Background, _) => {
+                for y in 0..height {
+                    let start = offset(0, y);
+                    self.0.rgba[start..start + width * PIXEL_SIZE].fill(0);
+                }
+            }
+            _ => {}
+        }
+        Ok(frame)
+    }
+}
+
+/// Steps through the frames of an [`Animation`].
+pub(crate) struct Player {
+    animation: Animation,
+    next: usize,
+    /// How many more times the animation is played, `None` when it loops.
+    plays_left: Option<u32>,
+}
+
+impl Player {
+    pub(crate) fn new(animation: Animation) -> crate::Result<Self> {
+        if animation.frames.is_empty() {
+            return Err(Error::InvalidAnimation("the animation has no frames"));
+        }
+        if animation.durations.len() != animation.frames.len() {
+            return Err(Error::InvalidAnimation(
+                "the animation must have one duration per frame",
+            ));
+        }
+        // frames shown for no time would keep the event loop busy rescheduling them
+        if animation.durations.contains(&Duration::ZERO) {
+            return Err(Error::InvalidAnimation(
+                "the frames of the animation must be shown for some time",
+            ));
+        }
+
+        Ok(Player {
+            plays_left: match animation.repeat {
+                Repeat::Infinite => None,
+                Repeat::Count(count) => Some(count.saturating_sub(1)),
+            },
+            animation,
+            next: 0,
+        })
+    }
+
+    /// Returns the next frame to show and for how long, or `None` once the animation is over.
+    pub(crate) fn next_frame(&mut self) -> Option<(Icon, Duration)> {
+        if self.next == self.animation.frames.len() {
+            match &mut self.plays_left {
+                Some(0) => return None,
+                Some(plays_left) => *plays_left -= 1,
+                None => {}
+            }
+            self.next = 0;
+        }
+
+        let frame = self.animation.frames[self.next].clone();
+        let duration = self.animation.durations[self.next];
+        self.next += 1;
+        Some((frame, duration))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(rgba: [u8; 4], width: u32, height: u32) -> Vec<u8> {
+        rgba.repeat((width * height) as usize)
+    }
+
+    #[test]
+    fn plays_frames_the_given_number_of_times() {
+        let icon = |value| Icon::from_rgba(vec![value; 4], 1, 1).unwrap();
+        let animation = Animation {
+            frames: vec![icon(1), icon(2)],
+            durations: vec![Duration::from_millis(10), Duration::from_millis(20)],
+            repeat: Repeat::Count(2),
+        };
+
+        let mut player = Player::new(animation.clone()).unwrap();
+        let mut played = Vec::new();
+        while let Some((frame, duration)) = player.next_frame() {
+            played.push((frame.to_rgba().unwrap()[0], duration.as_millis()));
+        }
+        assert_eq!(played, [(1, 10), (2, 20), (1, 10), (2, 20)]);
+
+        let mut player = Player::new(Animation {
+            repeat: Repeat::Infinite,
+            ..animation.clone()
+        })
+        .unwrap();
+        assert_eq!((0..5).filter_map(|_| player.next_frame()).count(), 5);
+
+        assert!(matches!(
+            Player::new(Animation {
+                durations: vec![Duration::ZERO],
+                ..animation.clone()
+            }),
+            Err(Error::InvalidAnimation(_))
+        ));
+        assert!(matches!(
+            Player::new(Animation {
+                durations: vec![Duration::from_millis(10), Duration::ZERO],
+                ..animation
+            }),
+            Err(Error::InvalidAnimation(_))
+        ));
+    }
+
+    #[test]
+    fn composes_apng_frames() {
+        let mut apng = Vec::new();
+        let mut encoder = png::Encoder::new(&mut apng, 2, 2);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_animated(3, 2).unwrap();
+        let mut writer = encoder.write_header().unwrap();
+
+        // a full red frame, kept for the next one
+        writer.set_frame_delay(1, 4).unwrap();
+        writer
+            .write_image_data(&solid([255, 0, 0, 255], 2, 2))
+            .unwrap();
+        // a half transparent blue pixel blended in the bottom-right corner, then cleared
+        writer.set_frame_dimension(1, 1).unwrap();
+        writer.set_frame_position(1, 1).unwrap();
+        writer.set_blend_op(png::BlendOp::Over).unwrap();
+        writer.set_dispose_op(png::DisposeOp::Background).unwrap();
+        writer.set_frame_delay(0, 0).unwrap();
+        writer.write_image_data(&[0, 0, 255, 51]).unwrap();
+        // a green pixel replacing the top-left corner, with a delay too short to be honored
+        writer.set_frame_position(0, 0).unwrap();
+        writer.set_frame_delay(1, 100).unwrap();
+        writer.set_blend_op(png::BlendOp::Source).unwrap();
+        writer.write_image_data(&[0, 255, 0, 255]).unwrap();
+        writer.finish().unwrap();
+
+        let animation = Animation::from_apng(&apng).unwrap();
+
+        let frames: Vec<_> = animation
+            .frames
+            .iter()
+            .map(|frame| frame.to_rgba().unwrap())
+            .collect();
+        assert_eq!(frames[0], solid([255, 0, 0, 255], 2, 2));
+        assert_eq!(
+            frames[1],
+            [solid([255, 0, 0, 255], 3, 1), vec![204, 0, 51, 255]].concat()
+        );
+        assert_eq!(
+            frames[2],
+            [
+                vec![0, 255, 0, 255],
+                solid([255, 0, 0, 255], 2, 1),
+                vec![0; 4]
+            ]
+            .concat()
+        );
+        assert_eq!(
+            animation.durations,
+            [250, 100, 100].map(Duration::from_millis)
+        );
+        assert_eq!(animation.repeat, Repeat::Count(2));
+
+        assert!(matches!(
+            Animation::from_apng(&apng[..apng.len() - 20]),
+            Err(BadIcon::DecodingError { format: "APNG", .. })
+        ));
+    }
+
+    #[cfg(feature = "gif")]
+    #[test]
+    fn composes_gif_frames() {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut bytes, 2, 1, &[]).unwrap();
+            encoder.set_repeat(gif::Repeat::Finite(1)).unwrap();
+
+            let mut first = gif::Frame::from_rgba(2, 1, &mut solid([255, 0, 0, 255], 2, 1));
+            first.delay = 5;
+            first.dispose = gif::DisposalMethod::Previous;
+            encoder.write_frame(&first).unwrap();
+
+            let mut second = gif::Frame::from_rgba(1, 1, &mut [0, 0, 255, 255]);
+            second.left = 1;
+            encoder.write_frame(&second).unwrap();
+        }
+
+        let animation = Animation::from_gif(&bytes).unwrap();
+
+        let frames: Vec<_> = animation
+            .frames
+            .iter()
+            .map(|frame| frame.to_rgba().unwrap())
+            .collect();
+        assert_eq!(frames[0], solid([255, 0, 0, 255], 2, 1));
+        // the first frame was disposed of, back to the transparent canvas
+        assert_eq!(frames[1], [vec![0; 4], vec![0, 0, 255, 255]].concat());
+        assert_eq!(animation.durations, [50, 100].map(Duration::from_millis));
+        assert_eq!(animation.repeat, Repeat::Count(2));
+    }
+}
@@ -13,8 +13,16 @@ pub enum Error {
     #[cfg(any(target_os = "linux", target_os = "macos"))]
     #[error(transparent)]
     PngEncodingError(#[from] png::EncodingError),
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    DBusError(#[from] zbus::Error),
+    #[cfg(target_os = "linux")]
+    #[error(transparent)]
+    X11Error(#[from] x11rb::errors::ReplyOrIdError),
     #[error("not on the main thread")]
     NotMainThread,
+    #[error("invalid animation: {0}")]
+    InvalidAnimation(&'static str),
 }
 
 /// Convenient type alias of Result type for tray-icon.
@@ -0,0 +1,442 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Small line, bar and area graphs drawn into icons, for trays showing the recent history
+//! of a value like CPU usage or network traffic.
+//!
+//! ```no_run
+//! use tray_icon::graph::{Graph, GraphKind, GraphStyle, Series};
+//! # let tray_icon: tray_icon::TrayIcon = unimplemented!();
+//!
+//! let mut graph = Graph::new(GraphStyle {
+//!     kind: GraphKind::Area,
+//!     min: Some(0.0),
+//!     max: Some(100.0),
+//!     ..Default::default()
+//! });
+//!
+//! let cpu_usage = [12.0, 30.5, 80.0, 64.2, 20.0];
+//! let icon = graph.render(&[Series::new(&cpu_usage, [37, 99, 235, 255])]).unwrap();
+//! tray_icon.set_icon(Some(icon)).unwrap();
+//! ```
+
+use crate::icon::{BadIcon, Icon, RgbaIcon, PIXEL_SIZE};
+
+/// How the values of a [`Graph`] are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GraphKind {
+    /// A line through the values.
+    #[default]
+    Line,
+    /// A bar for each value, bars of several series are drawn side by side.
+    Bar,
+    /// A line through the values with the area below it filled with a third
+    /// of the alpha of its color.
+    Area,
+}
+
+/// The look of a [`Graph`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GraphStyle {
+    /// Whether values are drawn as a line, bars or an area.
+    pub kind: GraphKind,
+    /// The width of the icon in pixels.
+    pub width: u32,
+    /// The height of the icon in pixels.
+    pub height: u32,
+    /// The RGBA color of the background.
+    pub background: [u8; 4],
+    /// The value at the bottom edge, or the smallest value of the series if `None`.
+    pub min: Option<f32>,
+    /// The value at the top edge, or the largest value of the series if `None`.
+    pub max: Option<f32>,
+    /// The width of the lines of [`GraphKind::Line`] and [`GraphKind::Area`] graphs, in pixels.
+    pub line_width: f32,
+}
+
+impl Default for GraphStyle {
+    fn default() -> Self {
+        Self {
+            kind: GraphKind::default(),
+            width: 32,
+            height: 32,
+            background: [0, 0, 0, 0],
+            min: None,
+            max: None,
+            line_width: 2.0,
+        }
+    }
+}
+
+/// Values drawn by a [`Graph`], oldest first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Series<'a> {
+    /// The values, spread over the width of the graph. Values that aren't finite leave a gap.
+    pub values: &'a [f32],
+    /// The RGBA color of the line or bars.
+    pub color: [u8; 4],
+}
+
+impl<'a> Series<'a> {
+    /// Creates a series of `values` drawn with `color`.
+    pub fn new(values: &'a [f32], color: [u8; 4]) -> Self {
+        Self { values, color }
+    }
+}
+
+/// Draws series of values into icons.
+///
+/// Series shorter than the longest one are aligned to the right edge, so histories that just
+/// started line up with the latest values of the others.
+///
+/// The pixels are drawn into a buffer kept between renders, so updating the graph every
+/// second doesn't reallocate it.
+#[derive(Debug, Clone)]
+pub struct Graph {
+    style: GraphStyle,
+    buffer: RgbaIcon,
+}
+
+impl Graph {
+    /// Creates a graph drawn with `style`.
+    pub fn new(style: GraphStyle) -> Self {
+        Self {
+            style,
+            buffer: RgbaIcon {
+                rgba: Vec::new(),
+                width: 0,
+                height: 0,
+            },
+        }
+    }
+
+    /// Returns the style the graph is drawn with.
+    pub fn style(&self) -> &GraphStyle {
+        &self.style
+    }
+
+    /// Changes the style of the next renders.
+    pub fn set_style(&mut self, style: GraphStyle) {
+        self.style = style;
+    }
+
+    /// Draws `series` in order, later series over earlier ones, and returns the icon.
+    ///
+    /// The icon gets a copy of the drawn pixels, since the tray keeps showing it
+    /// while the next values are drawn.
+    pub fn render(&mut self, series: &[Series]) -> Result<Icon, BadIcon> {
+        self.draw(series)?;
+        Icon::from_rgba(
+            self.buffer.rgba.clone(),
+            self.buffer.width,
+            self.buffer.height,
+        )
+    }
+
+    fn draw(&mut self, series: &[Series]) -> Result<(), BadIcon> {
+        let GraphStyle { width, height, .. } = self.style;
+        let len = width
+            .checked_mul(height)
+            .and_then(|pixels| pixels.checked_mul(PIXEL_SIZE as u32))
+            .filter(|len| *len != 0)
+            .ok_or(BadIcon::InvalidSize { width, height })?;
+
+        let buffer = &mut self.buffer;
+        buffer.rgba.resize(len as usize, 0);
+        (buffer.width, buffer.height) = (width, height);
+        for pixel in buffer.rgba.chunks_exact_mut(PIXEL_SIZE) {
+            pixel.copy_from_slice(&self.style.background);
+        }
+
+        let Some(scale) = Scale::new(&self.style, series) else {
+            return Ok(());
+        };
+        for (index, series) in series.iter().enumerate() {
+            let values = Values::new(series.values, &scale);
+            match self.style.kind {
+                GraphKind::Line => draw_line(buffer, &values, &scale, series.color),
+                GraphKind::Area => {
+                    let [r, g, b, a] = series.color;
+                    let fill = [r, g, b, (a as f32 / 3.0).round() as u8];
+                    draw_area(buffer, &values, &scale, fill);
+                    draw_line(buffer, &values, &scale, series.color);
+                }
+                GraphKind::Bar => draw_bars(buffer, &values, &scale, index, series.color),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Maps values and their indices to pixels.
+struct Scale {
+    min: f32,
+    max: f32,
+    /// Number of values of the longest series.
+    len: usize,
+    /// Width of the slot of each value.
+    slot: f32,
+    /// Number of series, for bars drawn side by side.
+    series: usize,
+    height: f32,
+    line_width: f32,
+}
+
+impl Scale {
+    /// Returns `None` when there is nothing to draw.
+    fn new(style: &GraphStyle, series: &[Series]) -> Option<Self> {
+        let len = series
+            .iter()
+            .map(|s| s.values.len())
+            .max()
+            .filter(|len| *len > 0)?;
+        let finite = || {
+            series
+                .iter()
+                .flat_map(|s| s.values)
+                .copied()
+                .filter(|v| v.is_finite())
+        };
+        let min = style.min.or_else(|| finite().reduce(f32::min))?;
+        let max = style.max.or_else(|| finite().reduce(f32::max))?;
+        // a flat series is drawn across the middle
+        let (min, max) = if max > min {
+            (min, max)
+        } else {
+            (min - 1.0, min + 1.0)
+        };
+
+        Some(Self {
+            min,
+            max,
+            len,
+            slot: style.width as f32 / len as f32,
+            series: series.len(),
+            height: style.height as f32,
+            line_width: style.line_width.max(0.0),
+        })
+    }
+
+    /// The top of the bar of `value`, or `None` for gaps.
+    fn bar_top(&self, value: f32) -> Option<f32> {
+        let value = value
+            .is_finite()
+            .then_some(value)?
+            .clamp(self.min, self.max);
+        Some((self.max - value) / (self.max - self.min) * self.height)
+    }
+
+    /// The center of the line at `value`, kept far enough from the edges
+    /// for the line to be drawn whole.
+    fn line_y(&self, value: f32) -> Option<f32> {
+        let inset = (self.line_width / 2.0).min(self.height / 2.0);
+        let top = self.bar_top(value)?;
+        Some(inset + top * (self.height - 2.0 * inset) / self.height)
+    }
+}
+
+/// The values of a series, aligned to the right of the longest one.
+struct Values<'a> {
+    values: &'a [f32],
+    offset: usize,
+}
+
+impl<'a> Values<'a> {
+    fn new(values: &'a [f32], scale: &Scale) -> Self {
+        Self {
+            values,
+            offset: scale.len - values.len(),
+        }
+    }
+
+    /// The value in the slot `index`, counted from the left edge.
+    fn get(&self, index: usize) -> Option<f32> {
+        index
+            .checked_sub(self.offset)
+            .and_then(|i| self.values.get(i))
+            .copied()
+            .filter(|v| v.is_finite())
+    }
+
+    /// The point of the line at the center of slot `index`.
+    fn point(&self, index: usize, scale: &Scale) -> Option<(f32, f32)> {
+        let y = scale.line_y(self.get(index)?)?;
+        Some(((index as f32 + 0.5) * scale.slot, y))
+    }
+}
+
+fn draw_line(icon: &mut RgbaIcon, values: &Values, scale: &Scale, color: [u8; 4]) {
+    let half = scale.line_width / 2.0;
+    let (width, height) = (icon.width, icon.height);
+    icon.draw_shape(0..width, 0..height, |x, y| {
+        // only the slots around `x` can have segments close enough
+        let first = ((x - half) / scale.slot - 1.0).floor().max(0.0) as usize;
+        let last = (((x + half) / scale.slot).ceil() as usize).min(scale.len - 1);
+        let near = (first..=last).any(|index| {
+            let Some(start) = values.point(index, scale) else {
+                return false;
+            };
+            // values next to gaps are drawn as dots
+            let end = values.point(index + 1, scale).unwrap_or(start);
+            distance_to_segment((x, y), start, end) <= half
+        });
+        near.then_some(color)
+    });
+}
+
+fn draw_area(icon: &mut RgbaIcon, values: &Values, scale: &Scale, color: [u8; 4]) {
+    let (width, height) = (icon.width, icon.height);
+    icon.draw_shape(0..width, 0..height, |x, y| {
+        let position = x / scale.slot - 0.5;
+        if position < 0.0 {
+            return None;
+        }
+        let index = position as usize;
+        let (start, end) = (values.point(index, scale)?, values.point(index + 1, scale)?);
+        let t = (x - start.0) / (end.0 - start.0);
+        (y >= start.1 + (end.1 - start.1) * t).then_some(color)
+    });
+}
+
+fn draw_bars(icon: &mut RgbaIcon, values: &Values, scale: &Scale, series: usize, color: [u8; 4]) {
+    // a pixel between slots when they are wide enough
+    let gap = if scale.slot >= 3.0 { 1.0 } else { 0.0 };
+    let bar = (scale.slot - gap) / scale.series as f32;
+    let (width, height) = (icon.width, icon.height);
+    icon.draw_shape(0..width, 0..height, |x, y| {
+        let index = (x / scale.slot) as usize;
+        let left = index as f32 * scale.slot + series as f32 * bar;
+        if x < left || x >= left + bar {
+            return None;
+        }
+        let top = scale.bar_top(values.get(index)?)?;
+        (y >= top).then_some(color)
+    });
+}
+
+fn distance_to_segment(point: (f32, f32), start: (f32, f32), end: (f32, f32)) -> f32 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (x, y) = (start.0 + dx * t - point.0, start.1 + dy * t - point.1);
+    (x * x + y * y).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icon::tests::assert_golden;
+
+    const BLUE: [u8; 4] = [37, 99, 235, 255];
+    const RED: [u8; 4] = [220, 38, 38, 255];
+
+    fn pixel(graph: &Graph, x: u32, y: u32) -> [u8; 4] {
+        let i = (y * graph.buffer.width + x) as usize * PIXEL_SIZE;
+        graph.buffer.rgba[i..i + PIXEL_SIZE].try_into().unwrap()
+    }
+
+    #[test]
+    fn matches_golden_images() {
+        let cpu = [10.0, 25.0, 60.0, 90.0, 70.0, 40.0, 55.0, 30.0];
+        let network = [5.0, 5.0, 20.0, f32::NAN, 35.0, 30.0, 80.0, 65.0];
+        let series = [Series::new(&cpu, BLUE), Series::new(&network, RED)];
+
+        for (name, kind) in [
+            ("graph-line", GraphKind::Line),
+            ("graph-bar", GraphKind::Bar),
+            ("graph-area", GraphKind::Area),
+        ] {
+            let mut graph = Graph::new(GraphStyle {
+                kind,
+                background: [30, 30, 30, 255],
+                min: Some(0.0),
+                max: Some(100.0),
+                ..Default::default()
+            });
+            graph.draw(&series).unwrap();
+            assert_golden(name, &graph.buffer);
+        }
+    }
+
+    #[test]
+    fn scales_values_to_the_height() {
+        let mut graph = Graph::new(GraphStyle {
+            kind: GraphKind::Bar,
+            width: 4,
+            height: 10,
+            min: Some(0.0),
+            max: Some(10.0),
+            ..Default::default()
+        });
+        graph
+            .draw(&[Series::new(&[5.0, 20.0, f32::NAN, -3.0], RED)])
+            .unwrap();
+
+        // one pixel per bar, too narrow for gaps
+        assert_eq!(pixel(&graph, 0, 4), [0; 4]);
+        assert_eq!(pixel(&graph, 0, 5), RED);
+        assert_eq!(pixel(&graph, 1, 0), RED, "values are clamped to `max`");
+        assert!((0..10).all(|y| pixel(&graph, 2, y) == [0; 4]));
+        assert!((0..10).all(|y| pixel(&graph, 3, y) == [0; 4]));
+
+        // bars of two series side by side, the shorter series is aligned to the right
+        // and the range fits the values
+        graph.set_style(GraphStyle {
+            width: 8,
+            min: None,
+            max: None,
+            ..*graph.style()
+        });
+        graph
+            .draw(&[
+                Series::new(&[1.0, 2.0, 3.0, 4.0], RED),
+                Series::new(&[4.0], BLUE),
+            ])
+            .unwrap();
+        assert_eq!(pixel(&graph, 0, 9), [0; 4], "the minimum is at the bottom");
+        assert_eq!(pixel(&graph, 6, 0), RED);
+        assert_eq!(pixel(&graph, 7, 0), BLUE);
+        assert_eq!(pixel(&graph, 5, 9), [0; 4]);
+    }
+
+    #[test]
+    fn reuses_the_buffer() {
+        let mut graph = Graph::new(GraphStyle::default());
+        graph.render(&[Series::new(&[1.0, 2.0], RED)]).unwrap();
+        let (buffer, capacity) = (graph.buffer.rgba.as_ptr(), graph.buffer.rgba.capacity());
+
+        let icon = graph
+            .render(&[Series::new(&[2.0, 1.0, 3.0], BLUE)])
+            .unwrap();
+        assert_eq!(graph.buffer.rgba.as_ptr(), buffer);
+        assert_eq!(graph.buffer.rgba.capacity(), capacity);
+        assert_eq!((icon.width().unwrap(), icon.height().unwrap()), (32, 32));
+        assert_eq!(icon.to_rgba().unwrap(), graph.buffer.rgba);
+
+        assert!(graph
+            .render(&[])
+            .unwrap()
+            .to_rgba()
+            .unwrap()
+            .iter()
+            .all(|c| *c == 0));
+        for (width, height) in [(0, 32), (1 << 16, 1 << 16)] {
+            graph.set_style(GraphStyle {
+                width,
+                height,
+                ..GraphStyle::default()
+            });
+            assert!(matches!(
+                graph.render(&[]),
+                Err(BadIcon::InvalidSize { .. })
+            ));
+        }
+    }
+}
@@ -4,11 +4,25 @@
 
 // taken from https://github.com/rust-windowing/winit/blob/92fdf5ba85f920262a61cee4590f4a11ad5738d1/src/icon.rs
 
-use crate::platform_impl::PlatformIcon;
-use std::{error::Error, fmt, io, mem};
+use crate::{platform_impl::PlatformIcon, Rect};
+use std::{borrow::Cow, error::Error, fmt, io, mem, ops::Range, path::Path};
+
+mod badge;
+mod icns;
+mod ico;
+mod ops;
+mod progress;
+#[cfg(feature = "text")]
+mod text;
+
+pub use self::badge::{Badge, BadgePosition};
+pub use self::ops::Filter;
+pub use self::progress::{Progress, ProgressStyle};
+#[cfg(feature = "text")]
+pub use self::text::TextIconStyle;
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct Pixel {
     pub(crate) r: u8,
     pub(crate) g: u8,
@@ -18,6 +32,12 @@ pub(crate) struct Pixel {
 
 pub(crate) const PIXEL_SIZE: usize = mem::size_of::<Pixel>();
 
+/// Samples along each side of a pixel in [`RgbaIcon::draw_shape`].
+const SAMPLES: u32 = 4;
+
+/// The first bytes of every PNG file.
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
 #[derive(Debug)]
 /// An error produced when using [`Icon::from_rgba`] with invalid arguments.
 pub enum BadIcon {
@@ -34,6 +54,23 @@ pub enum BadIcon {
     },
     /// Produced when underlying OS functionality failed to create the icon
     OsError(io::Error),
+    /// Produced when the icon data is malformed, e.g. a truncated PNG file.
+    DecodingError {
+        /// The format the data was decoded as, e.g. `"PNG"`.
+        format: &'static str,
+        reason: String,
+    },
+    /// Produced when the icon file isn't in a format that can be loaded on this platform.
+    UnsupportedFormat,
+    /// Produced when [`Icon::from_sizes`] is given no images.
+    NoImages,
+    /// Produced when an image operation like [`Icon::cropped`] would result in an empty icon.
+    InvalidSize { width: u32, height: u32 },
+    /// Produced when the text of a [`Badge`] or `Icon::from_text` has a character
+    /// the font doesn't have.
+    UnsupportedCharacter { character: char },
+    /// Produced when `Icon::from_text` is given no characters or more than four.
+    InvalidTextLength { length: usize },
 }
 
 impl fmt::Display for BadIcon {
@@ -53,6 +90,22 @@ impl fmt::Display for BadIcon {
                 width, height, pixel_count, width_x_height,
             ),
             BadIcon::OsError(e) => write!(f, "OS error when instantiating the icon: {:?}", e),
+            BadIcon::DecodingError { format, reason } => {
+                write!(f, "Failed to decode the {} icon: {}", format, reason)
+            }
+            BadIcon::UnsupportedFormat => write!(f, "The icon file format isn't supported."),
+            BadIcon::NoImages => write!(f, "The icon doesn't contain any image."),
+            BadIcon::InvalidSize { width, height } => {
+                write!(f, "The resulting icon size ({}x{}) is empty.", width, height)
+            }
+            BadIcon::UnsupportedCharacter { character } => {
+                write!(f, "The font has no {:?} character.", character)
+            }
+            BadIcon::InvalidTextLength { length } => write!(
+                f,
+                "The text has {} characters, between 1 and 4 are supported.",
+                length
+            ),
         }
     }
 }
@@ -70,6 +123,245 @@ pub(crate) struct RgbaIcon {
     pub(crate) height: u32,
 }
 
+#[allow(dead_code)] // Not used on every platform
+impl RgbaIcon {
+    /// Encodes the icon as an 8-bit RGBA PNG image.
+    pub(crate) fn to_png(&self) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        // the dimensions and buffer size are validated when the icon is created,
+        // and writing to a `Vec` can't fail
+        let mut writer = encoder.write_header().expect("failed to write PNG header");
+        writer
+            .write_image_data(&self.rgba)
+            .expect("failed to write PNG data");
+        writer.finish().expect("failed to write PNG data");
+        png
+    }
+
+    /// Scales the icon down to `width`x`height` by averaging the pixels covered by
+    /// each destination pixel, weighting colors by their alpha to avoid dark fringes.
+    /// An empty icon scales to transparent pixels.
+    pub(crate) fn downscaled(&self, width: u32, height: u32) -> RgbaIcon {
+        let (src_width, src_height) = (self.width as usize, self.height as usize);
+        let (width, height) = (width.max(1), height.max(1));
+        if self.rgba.is_empty() {
+            return RgbaIcon {
+                rgba: vec![0; width as usize * height as usize * PIXEL_SIZE],
+                width,
+                height,
+            };
+        }
+        let mut rgba = Vec::with_capacity(width as usize * height as usize * PIXEL_SIZE);
+
+        for y in 0..height as usize {
+            let y0 = y * src_height / height as usize;
+            let y1 = ((y + 1) * src_height / height as usize).max(y0 + 1);
+            for x in 0..width as usize {
+                let x0 = x * src_width / width as usize;
+                let x1 = ((x + 1) * src_width / width as usize).max(x0 + 1);
+
+                let mut sum = [0u64; 4];
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        let i = (sy * src_width + sx) * PIXEL_SIZE;
+                        let a = self.rgba[i + 3] as u64;
+                        sum[0] += self.rgba[i] as u64 * a;
+                        sum[1] += self.rgba[i + 1] as u64 * a;
+                        sum[2] += self.rgba[i + 2] as u64 * a;
+                        sum[3] += a;
+                    }
+                }
+
+                let count = ((y1 - y0) * (x1 - x0)) as u64;
+                let alpha = sum[3];
+                let color = |c: u64| c.checked_div(alpha).unwrap_or(0) as u8;
+                rgba.extend_from_slice(&[
+                    color(sum[0]),
+                    color(sum[1]),
+                    color(sum[2]),
+                    (alpha / count) as u8,
+                ]);
+            }
+        }
+
+        RgbaIcon {
+            rgba,
+            width,
+            height,
+        }
+    }
+
+    /// Draws `overlay` over the bottom-right corner of the icon, scaling it down
+    /// to at most half the icon size. The icon is kept as is if either of them is empty.
+    pub(crate) fn with_overlay(&self, overlay: &RgbaIcon) -> RgbaIcon {
+        if self.rgba.is_empty() || overlay.rgba.is_empty() {
+            return self.clone();
+        }
+        let (max_width, max_height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        let scaled;
+        let overlay = if overlay.width > max_width || overlay.height > max_height {
+            // keep the aspect ratio, the tighter dimension decides the scale
+            let (width, height) = if overlay.width * max_height > overlay.height * max_width {
+                (max_width, overlay.height * max_width / overlay.width)
+            } else {
+                (overlay.width * max_height / overlay.height, max_height)
+            };
+            scaled = overlay.downscaled(width, height);
+            &scaled
+        } else {
+            overlay
+        };
+
+        let mut rgba = self.rgba.clone();
+        let (x0, y0) = (self.width - overlay.width, self.height - overlay.height);
+        for y in 0..overlay.height {
+            for x in 0..overlay.width {
+                let src = (y * overlay.width + x) as usize * PIXEL_SIZE;
+                let dst = ((y0 + y) * self.width + x0 + x) as usize * PIXEL_SIZE;
+                let src = &overlay.rgba[src..src + PIXEL_SIZE];
+                blend_over(&mut rgba[dst..dst + PIXEL_SIZE], src);
+            }
+        }
+
+        RgbaIcon {
+            rgba,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Blends a shape over the pixels in `columns` and `rows`, antialiased from several samples
+    /// in each pixel. `color_at` returns the color of the shape at a point, in pixels from the
+    /// top-left corner, or `None` outside of it.
+    pub(crate) fn draw_shape(
+        &mut self,
+        columns: Range<u32>,
+        rows: Range<u32>,
+        color_at: impl Fn(f32, f32) -> Option<[u8; PIXEL_SIZE]>,
+    ) {
+        let columns = columns.start..columns.end.min(self.width);
+        for y in rows.start..rows.end.min(self.height) {
+            for x in columns.clone() {
+                // premultiplied sum of the samples
+                let mut sum = [0.0f32; PIXEL_SIZE];
+                for sy in 0..SAMPLES {
+                    for sx in 0..SAMPLES {
+                        let sample = color_at(
+                            x as f32 + (sx as f32 + 0.5) / SAMPLES as f32,
+                            y as f32 + (sy as f32 + 0.5) / SAMPLES as f32,
+                        );
+                        if let Some(color) = sample {
+                            let alpha = color[3] as f32;
+                            for i in 0..3 {
+                                sum[i] += color[i] as f32 * alpha;
+                            }
+                            sum[3] += alpha;
+                        }
+                    }
+                }
+                if sum[3] <= 0.0 {
+                    continue;
+                }
+
+                let color = [
+                    (sum[0] / sum[3]).round() as u8,
+                    (sum[1] / sum[3]).round() as u8,
+                    (sum[2] / sum[3]).round() as u8,
+                    (sum[3] / (SAMPLES * SAMPLES) as f32).round() as u8,
+                ];
+                let i = (y * self.width + x) as usize * PIXEL_SIZE;
+                blend_over(&mut self.rgba[i..i + PIXEL_SIZE], &color);
+            }
+        }
+    }
+}
+
+/// Picks the image to draw at `size` pixels from several sizes of the same icon:
+/// the smallest one at least that large, which scales down well, or else the largest one.
+pub(crate) fn best_for_size<'a>(
+    images: impl IntoIterator<Item = &'a RgbaIcon>,
+    size: u32,
+) -> Option<&'a RgbaIcon> {
+    let side = |icon: &RgbaIcon| icon.width.max(icon.height);
+    images.into_iter().min_by_key(|icon| {
+        let side = side(icon);
+        // larger images first, closest to `size` in each group
+        (side < size, side.abs_diff(size))
+    })
+}
+
+/// Composites the non-premultiplied `src` pixel over `dst`.
+pub(crate) fn blend_over(dst: &mut [u8], src: &[u8]) {
+    let src_a = src[3] as u32;
+    let dst_a = dst[3] as u32 * (255 - src_a) / 255;
+    let out_a = src_a + dst_a;
+    if out_a == 0 {
+        dst.copy_from_slice(&[0; PIXEL_SIZE]);
+        return;
+    }
+
+    for i in 0..3 {
+        dst[i] = ((src[i] as u32 * src_a + dst[i] as u32 * dst_a) / out_a) as u8;
+    }
+    dst[3] = out_a as u8;
+}
+
+/// A vector icon, kept around so it can be rendered at every size a platform needs.
+#[cfg(feature = "svg")]
+#[derive(Debug, Clone)]
+pub(crate) struct SvgIcon(std::sync::Arc<resvg::usvg::Tree>);
+
+#[cfg(feature = "svg")]
+#[allow(dead_code)] // Not used on every platform
+impl SvgIcon {
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Self, BadIcon> {
+        let tree =
+            resvg::usvg::Tree::from_data(bytes, &resvg::usvg::Options::default()).map_err(|e| {
+                BadIcon::DecodingError {
+                    format: "SVG",
+                    reason: e.to_string(),
+                }
+            })?;
+        Ok(Self(std::sync::Arc::new(tree)))
+    }
+
+    /// Renders the icon with its longest side `size` pixels long.
+    pub(crate) fn rasterize(&self, size: u32) -> RgbaIcon {
+        let svg_size = self.0.size();
+        let scale = size.max(1) as f32 / svg_size.width().max(svg_size.height());
+        let width = ((svg_size.width() * scale).round() as u32).max(1);
+        let height = ((svg_size.height() * scale).round() as u32).max(1);
+
+        let mut pixmap =
+            resvg::tiny_skia::Pixmap::new(width, height).expect("pixmap dimensions are never zero");
+        resvg::render(
+            &self.0,
+            resvg::tiny_skia::Transform::from_scale(scale, scale),
+            &mut pixmap.as_mut(),
+        );
+
+        // tiny-skia pixels are premultiplied
+        let rgba = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|p| {
+                let c = p.demultiply();
+                [c.red(), c.green(), c.blue(), c.alpha()]
+            })
+            .collect();
+
+        RgbaIcon {
+            rgba,
+            width,
+            height,
+        }
+    }
+}
+
 /// For platforms which don't have window icons (e.g. web)
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct NoIcon;
@@ -103,6 +395,47 @@ mod constructors {
         }
     }
 
+    impl RgbaIcon {
+        /// Decodes a PNG image of any color type and bit depth into 8-bit RGBA.
+        pub fn from_png_bytes(bytes: &[u8]) -> Result<Self, BadIcon> {
+            let decoding_error = |e: png::DecodingError| BadIcon::DecodingError {
+                format: "PNG",
+                reason: e.to_string(),
+            };
+
+            let mut decoder = png::Decoder::new(bytes);
+            decoder.set_transformations(png::Transformations::normalize_to_color8());
+
+            let mut reader = decoder.read_info().map_err(decoding_error)?;
+            let mut buf = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut buf).map_err(decoding_error)?;
+            buf.truncate(info.buffer_size());
+
+            RgbaIcon::from_png_frame(&buf, &info)
+        }
+
+        /// Converts a frame decoded with the `normalize_to_color8` transformations to RGBA.
+        pub(crate) fn from_png_frame(buf: &[u8], info: &png::OutputInfo) -> Result<Self, BadIcon> {
+            let rgba = match info.color_type {
+                png::ColorType::Rgba => buf.to_vec(),
+                png::ColorType::Rgb => buf
+                    .chunks_exact(3)
+                    .flat_map(|p| [p[0], p[1], p[2], 255])
+                    .collect(),
+                png::ColorType::GrayscaleAlpha => buf
+                    .chunks_exact(2)
+                    .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                    .collect(),
+                // indexed images are expanded to RGB or RGBA by the transformations
+                png::ColorType::Grayscale | png::ColorType::Indexed => {
+                    buf.iter().flat_map(|&g| [g, g, g, 255]).collect()
+                }
+            };
+
+            RgbaIcon::from_rgba(rgba, info.width, info.height)
+        }
+    }
+
     impl NoIcon {
         pub fn from_rgba(rgba: Vec<u8>, width: u32, height: u32) -> Result<Self, BadIcon> {
             // Create the rgba icon anyway to validate the input
@@ -135,20 +468,144 @@ impl Icon {
         })
     }
 
+    /// Creates an icon from its name in the freedesktop icon theme, like `network-wireless`.
+    ///
+    /// Hosts that support icon names look the icon up in the user's theme themselves,
+    /// otherwise the icon is loaded from the current theme following the
+    /// [icon theme spec](https://specifications.freedesktop.org/icon-theme-spec/latest/).
+    /// PNG and SVG icons are found, this returns an error if the theme has neither for `name`.
+    /// SVG icons are only rendered with the `svg` feature, without it hosts that
+    /// need pixels get a transparent icon.
+    ///
+    /// The current theme is read from the GTK settings, so this should be called on the GTK thread.
+    #[cfg(target_os = "linux")]
+    pub fn from_theme_name(name: &str) -> Result<Self, BadIcon> {
+        Ok(Icon {
+            inner: PlatformIcon::from_theme_name(name)?,
+        })
+    }
+
+    /// Creates an icon from several sizes of the same image, each as 32bpp RGBA data
+    /// with its width and height.
+    ///
+    /// The size that best matches what the platform draws is used, scaled down if needed.
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **Linux:** Every size is sent to the host, which picks the one to draw.
+    /// - **Windows:** The size closest to the notification area icon size for the current DPI is used.
+    /// - **macOS:** The size closest to the status bar height on Retina displays is used.
+    pub fn from_sizes(images: Vec<(Vec<u8>, u32, u32)>) -> Result<Self, BadIcon> {
+        let images = images
+            .into_iter()
+            .map(|(rgba, width, height)| RgbaIcon::from_rgba(rgba, width, height))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::from_images(images)
+    }
+
+    fn from_images(mut images: Vec<RgbaIcon>) -> Result<Self, BadIcon> {
+        if images.is_empty() {
+            return Err(BadIcon::NoImages);
+        }
+        images.sort_by_key(|icon| std::cmp::Reverse(icon.width.max(icon.height)));
+
+        Ok(Icon {
+            inner: PlatformIcon::from_sizes(images)?,
+        })
+    }
+
+    /// Creates an icon from the bytes of a Windows `.ico` file.
+    ///
+    /// Every image in the file is loaded, both PNG-compressed and bitmap ones,
+    /// and used like the sizes given to [`Icon::from_sizes`].
+    pub fn from_ico_bytes(bytes: &[u8]) -> Result<Self, BadIcon> {
+        Self::from_images(ico::decode(bytes)?)
+    }
+
+    /// Creates an icon from the bytes of an Apple `.icns` file.
+    ///
+    /// Every PNG, ARGB and legacy RGB image in the file is loaded and used like the sizes given
+    /// to [`Icon::from_sizes`]. JPEG 2000 images aren't supported and are skipped.
+    pub fn from_icns_bytes(bytes: &[u8]) -> Result<Self, BadIcon> {
+        Self::from_images(icns::decode(bytes)?)
+    }
+
+    /// Creates an icon from the bytes of a PNG image.
+    pub fn from_png_bytes(bytes: &[u8]) -> Result<Self, BadIcon> {
+        let icon = RgbaIcon::from_png_bytes(bytes)?;
+        Self::from_rgba(icon.rgba, icon.width, icon.height)
+    }
+
+    /// Creates an icon from the bytes of an SVG image.
+    ///
+    /// The image is kept as is and rendered at the sizes the platform needs, e.g. several sizes
+    /// for the StatusNotifierItem `IconPixmap` on Linux or the small icon size on Windows.
+    #[cfg(feature = "svg")]
+    pub fn from_svg(bytes: &[u8]) -> Result<Self, BadIcon> {
+        Ok(Icon {
+            inner: PlatformIcon::from_svg(SvgIcon::from_bytes(bytes)?)?,
+        })
+    }
+
+    /// Creates a square icon showing one to four characters of `text`, like a temperature
+    /// or an unread count.
+    ///
+    /// The text is drawn on a single line with the TrueType or OpenType font of `style`,
+    /// scaled to fill the icon inside the padding and centered. Glyphs stored as PNG images,
+    /// like in color emoji fonts, keep their colors and other glyphs are drawn with the
+    /// foreground color. The text isn't shaped, so zero width joiners and variation selectors
+    /// are skipped.
+    ///
+    /// The text is rendered without the platform text APIs, so icons are the same on
+    /// every platform.
+    #[cfg(feature = "text")]
+    pub fn from_text(text: &str, style: TextIconStyle) -> Result<Self, BadIcon> {
+        let icon = RgbaIcon::from_text(text, &style)?;
+        Self::from_rgba(icon.rgba, icon.width, icon.height)
+    }
+
     /// Create an icon from a file path.
     ///
+    /// PNG, `.ico` and `.icns` files are supported on every platform, and `.svg` files
+    /// when the `svg` feature is enabled.
+    ///
     /// Specify `size` to load a specific icon size from the file, or `None` to load the default
-    /// icon size from the file.
+    /// icon size from the file. Only Windows uses `size`, otherwise every size in the file is
+    /// loaded like with [`Icon::from_ico_bytes`].
     ///
-    /// In cases where the specified size does not exist in the file, Windows may perform scaling
-    /// to get an icon of the desired size.
-    #[cfg(windows)]
-    pub fn from_path<P: AsRef<std::path::Path>>(
-        path: P,
-        size: Option<(u32, u32)>,
-    ) -> Result<Self, BadIcon> {
-        let win_icon = PlatformIcon::from_path(path, size)?;
-        Ok(Icon { inner: win_icon })
+    /// ## Platform-specific
+    ///
+    /// - **Windows:** `.ico` files are loaded by the system. In cases where the specified size
+    ///   does not exist in the file, Windows may perform scaling to get an icon of the desired size.
+    pub fn from_path<P: AsRef<Path>>(path: P, size: Option<(u32, u32)>) -> Result<Self, BadIcon> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(BadIcon::OsError)?;
+        if bytes.starts_with(&PNG_SIGNATURE) {
+            return Self::from_png_bytes(&bytes);
+        }
+        if icns::is_icns(&bytes) {
+            return Self::from_icns_bytes(&bytes);
+        }
+        #[cfg(feature = "svg")]
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("svg"))
+        {
+            return Self::from_svg(&bytes);
+        }
+
+        #[cfg(windows)]
+        return Ok(Icon {
+            inner: PlatformIcon::from_path(path, size)?,
+        });
+        #[cfg(not(windows))]
+        {
+            let _ = size;
+            if ico::is_ico(&bytes) {
+                return Self::from_ico_bytes(&bytes);
+            }
+            Err(BadIcon::UnsupportedFormat)
+        }
     }
 
     /// Create an icon from a resource embedded in this executable or library.
@@ -181,4 +638,275 @@ impl Icon {
         let win_icon = PlatformIcon::from_handle(handle as _);
         Icon { inner: win_icon }
     }
+
+    /// Returns the width of the icon in pixels.
+    ///
+    /// For icons with several sizes, this is the size the platform uses, see [`Icon::to_rgba`],
+    /// which returns the same errors.
+    pub fn width(&self) -> Result<u32, BadIcon> {
+        Ok(self.size()?.0)
+    }
+
+    /// Returns the height of the icon in pixels.
+    ///
+    /// For icons with several sizes, this is the size the platform uses, see [`Icon::to_rgba`],
+    /// which returns the same errors.
+    pub fn height(&self) -> Result<u32, BadIcon> {
+        Ok(self.size()?.1)
+    }
+
+    /// Returns the width and height of the icon, only reading the pixels back
+    /// when they aren't stored.
+    pub(crate) fn size(&self) -> Result<(u32, u32), BadIcon> {
+        if let Some(icon) = self.inner.rgba_icon() {
+            return Ok((icon.width, icon.height));
+        }
+        let icon = self.inner.to_rgba_icon()?;
+        Ok((icon.width, icon.height))
+    }
+
+    /// Returns the pixels of the icon as 32bpp RGBA data, [`Icon::width`] by [`Icon::height`].
+    ///
+    /// ## Platform-specific
+    ///
+    /// - **Linux:** For icons with several sizes, the largest size is returned.
+    ///   Vector icons are rendered at 96 pixels.
+    /// - **Windows:** For icons with several sizes, the size closest to the notification area
+    ///   icon size is returned. The pixels of icons loaded by the system are read back from the
+    ///   icon handle, which returns an error if that fails.
+    /// - **macOS:** For icons with several sizes, the size closest to twice the status bar
+    ///   height is returned.
+    pub fn to_rgba(&self) -> Result<Vec<u8>, BadIcon> {
+        Ok(self.inner.to_rgba_icon()?.into_owned().rgba)
+    }
+
+    /// Encodes the pixels returned by [`Icon::to_rgba`] as a PNG image.
+    pub fn to_png(&self) -> Result<Vec<u8>, BadIcon> {
+        Ok(self.inner.to_rgba_icon()?.to_png())
+    }
+
+    /// Returns a copy of the icon scaled to `width`x`height` pixels with `filter`.
+    ///
+    /// Like the other image operations, this works on the pixels returned by
+    /// [`Icon::to_rgba`] and the resulting icon has a single size.
+    pub fn resized(&self, width: u32, height: u32, filter: Filter) -> Result<Icon, BadIcon> {
+        self.map(|icon| icon.resized(width, height, filter))
+    }
+
+    /// Returns a copy of the icon centered in a transparent square as wide as its longest side.
+    pub fn padded_to_square(&self) -> Result<Icon, BadIcon> {
+        self.map(|icon| Ok(icon.padded_to_square()))
+    }
+
+    /// Returns the part of the icon inside `rect`, in pixels from the top-left corner.
+    ///
+    /// `rect` is clamped to the icon bounds, this returns [`BadIcon::InvalidSize`]
+    /// if nothing is left.
+    pub fn cropped(&self, rect: Rect) -> Result<Icon, BadIcon> {
+        self.map(|icon| icon.cropped(rect))
+    }
+
+    /// Returns a copy of the icon with every pixel painted with the `rgba` color, keeping its
+    /// shape through the alpha channel. Useful for monochrome icons matching the panel theme.
+    pub fn tinted(&self, rgba: [u8; 4]) -> Result<Icon, BadIcon> {
+        self.map(|icon| Ok(icon.tinted(rgba)))
+    }
+
+    /// Returns a grayscale copy of the icon.
+    pub fn grayscale(&self) -> Result<Icon, BadIcon> {
+        self.map(|icon| Ok(icon.grayscale()))
+    }
+
+    /// Returns a copy of the icon with `badge` drawn over one of its corners.
+    ///
+    /// The badge is about half as high as the icon, or smaller when its text wouldn't fit
+    /// otherwise. Its text is drawn with a bundled bitmap font that only has digits, `+`, `-`
+    /// and `!`, other characters return [`BadIcon::UnsupportedCharacter`].
+    ///
+    /// Unlike the other image operations, the badge is drawn onto every size of the icon
+    /// the platform uses, so it stays sharp at each of them.
+    pub fn with_badge(&self, badge: Badge) -> Result<Icon, BadIcon> {
+        self.decorated(None, Some(&badge))
+    }
+
+    /// Returns a copy of the icon with `progress` drawn over it, as a ring along its edges
+    /// or a bar along its bottom edge.
+    ///
+    /// Like [`Icon::with_badge`], the progress is drawn onto every size of the icon
+    /// the platform uses.
+    pub fn with_progress(&self, progress: Progress) -> Result<Icon, BadIcon> {
+        self.decorated(Some(&progress), None)
+    }
+
+    /// Draws `progress`, then `badge` over it, onto every size of the icon the platform uses.
+    pub(crate) fn decorated(
+        &self,
+        progress: Option<&Progress>,
+        badge: Option<&Badge>,
+    ) -> Result<Icon, BadIcon> {
+        let images = self
+            .inner
+            .exported_images()
+            .into_iter()
+            .map(|icon| {
+                let icon = match progress {
+                    Some(progress) => Cow::Owned(icon.with_progress(progress)),
+                    None => icon,
+                };
+                match badge {
+                    Some(badge) => icon.with_badge(badge),
+                    None => Ok(icon.into_owned()),
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Self::from_images(images)
+    }
+
+    fn map(&self, f: impl FnOnce(&RgbaIcon) -> Result<RgbaIcon, BadIcon>) -> Result<Icon, BadIcon> {
+        let icon = f(&*self.inner.to_rgba_icon()?)?;
+        Icon::from_rgba(icon.rgba, icon.width, icon.height)
+    }
+}
+
+#[allow(dead_code)] // Not used on every platform
+impl Icon {
+    /// Returns a copy of the icon with `overlay` drawn over its bottom-right corner,
+    /// or `None` if the pixels of either icon aren't available.
+    pub(crate) fn with_overlay(&self, overlay: &Icon) -> Option<Icon> {
+        let rgba = self
+            .inner
+            .rgba_icon()?
+            .with_overlay(overlay.inner.rgba_icon()?);
+        Icon::from_rgba(rgba.rgba, rgba.width, rgba.height).ok()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::PathBuf;
+
+    use super::{BadIcon, Icon, RgbaIcon};
+
+    /// Compares `icon` to `src/icon/golden/{name}.png`, set `UPDATE_GOLDEN=1`
+    /// to write the current output instead.
+    pub(crate) fn assert_golden(name: &str, icon: &RgbaIcon) {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "src/icon/golden", name]
+            .iter()
+            .collect::<PathBuf>()
+            .with_extension("png");
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, icon.to_png()).unwrap();
+            return;
+        }
+
+        let golden = std::fs::read(&path)
+            .unwrap_or_else(|e| panic!("failed to read {}: {e}", path.display()));
+        let golden = RgbaIcon::from_png_bytes(&golden).unwrap();
+        assert_eq!(
+            (icon.width, icon.height),
+            (golden.width, golden.height),
+            "size of `{name}`"
+        );
+        assert!(icon.rgba == golden.rgba, "pixels of `{name}` changed");
+    }
+
+    /// The 32x32 example icon.
+    pub(super) fn source() -> RgbaIcon {
+        RgbaIcon::from_png_bytes(include_bytes!("../examples/icon.png")).unwrap()
+    }
+
+    fn encode_png(data: &[u8], width: u32, height: u32, color: png::ColorType) -> Vec<u8> {
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(data).unwrap();
+        writer.finish().unwrap();
+        png
+    }
+
+    #[test]
+    fn picks_smallest_larger_size() {
+        let icon = |size: u32| RgbaIcon::from_rgba(vec![0; (size * size * 4) as usize], size, size);
+        let images = [icon(64), icon(32), icon(16)].map(Result::unwrap);
+        let best = |size| super::best_for_size(&images, size).unwrap().width;
+
+        assert_eq!(best(16), 16);
+        assert_eq!(best(22), 32);
+        assert_eq!(best(32), 32);
+        assert_eq!(best(128), 64);
+        assert_eq!(best(8), 16);
+    }
+
+    #[test]
+    fn decodes_png_to_rgba() {
+        let rgb = encode_png(&[1, 2, 3, 4, 5, 6], 2, 1, png::ColorType::Rgb);
+        let icon = RgbaIcon::from_png_bytes(&rgb).unwrap();
+        assert_eq!((icon.width, icon.height), (2, 1));
+        assert_eq!(icon.rgba, [1, 2, 3, 255, 4, 5, 6, 255]);
+
+        let gray = encode_png(&[7, 128], 1, 1, png::ColorType::GrayscaleAlpha);
+        let icon = RgbaIcon::from_png_bytes(&gray).unwrap();
+        assert_eq!(icon.rgba, [7, 7, 7, 128]);
+
+        let truncated = &rgb[..rgb.len() - 20];
+        assert!(matches!(
+            RgbaIcon::from_png_bytes(truncated),
+            Err(BadIcon::DecodingError { format: "PNG", .. })
+        ));
+    }
+
+    #[test]
+    fn reads_back_pixels_and_png() {
+        let rgba = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12].repeat(2);
+        let icon = Icon::from_rgba(rgba.clone(), 3, 2).unwrap();
+
+        assert_eq!((icon.width().unwrap(), icon.height().unwrap()), (3, 2));
+        assert_eq!(icon.to_rgba().unwrap(), rgba);
+
+        let png = Icon::from_png_bytes(&icon.to_png().unwrap()).unwrap();
+        assert_eq!((png.width().unwrap(), png.height().unwrap()), (3, 2));
+        assert_eq!(png.to_rgba().unwrap(), rgba);
+    }
+
+    #[test]
+    fn overlay_is_scaled_into_bottom_right_corner() {
+        let base = RgbaIcon::from_rgba([0, 0, 255, 255].repeat(16), 4, 4).unwrap();
+        let overlay = RgbaIcon::from_rgba([255, 0, 0, 255].repeat(16), 4, 4).unwrap();
+
+        let icon = base.with_overlay(&overlay);
+
+        assert_eq!((icon.width, icon.height), (4, 4));
+        for (i, pixel) in icon.rgba.chunks(4).enumerate() {
+            let (x, y) = (i % 4, i / 4);
+            let expected = if x >= 2 && y >= 2 {
+                [255, 0, 0, 255]
+            } else {
+                [0, 0, 255, 255]
+            };
+            assert_eq!(pixel, expected, "pixel at {x},{y}");
+        }
+    }
+
+    #[test]
+    fn overlay_is_blended_by_alpha() {
+        let base = RgbaIcon::from_rgba([0, 0, 255, 255].repeat(4), 2, 2).unwrap();
+        let overlay = RgbaIcon::from_rgba(vec![255, 0, 0, 51], 1, 1).unwrap();
+
+        let icon = base.with_overlay(&overlay);
+
+        assert_eq!(&icon.rgba[12..], [51, 0, 204, 255]);
+        assert_eq!(&icon.rgba[..12], [0, 0, 255, 255].repeat(3));
+    }
+
+    #[test]
+    fn overlay_on_or_of_empty_icon_keeps_the_base() {
+        let empty = RgbaIcon::from_rgba(vec![], 0, 0).unwrap();
+        let base = RgbaIcon::from_rgba([0, 0, 255, 255].repeat(4), 2, 2).unwrap();
+
+        assert_eq!(empty.with_overlay(&base), empty);
+        assert_eq!(base.with_overlay(&empty), base);
+        assert_eq!(empty.downscaled(2, 1).rgba, [0; 8]);
+    }
 }
@@ -0,0 +1,217 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Badges drawn by [`Icon::with_badge`](super::Icon::with_badge).
+
+use super::{blend_over, BadIcon, RgbaIcon};
+
+/// A short text, usually a count, drawn in a rounded badge over a corner of an icon.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Badge {
+    /// The text of the badge, made of digits, `+`, `-` and `!`.
+    /// An empty text draws a dot.
+    pub text: String,
+    /// The RGBA color of the badge.
+    pub background: [u8; 4],
+    /// The RGBA color of the text.
+    pub foreground: [u8; 4],
+    /// The corner of the icon the badge is drawn over.
+    pub position: BadgePosition,
+}
+
+impl Badge {
+    /// Creates a white on red badge over the top-right corner.
+    pub fn new<S: Into<String>>(text: S) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Badge {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            background: [220, 38, 38, 255],
+            foreground: [255, 255, 255, 255],
+            position: BadgePosition::default(),
+        }
+    }
+}
+
+/// The corner of the icon a [`Badge`] is drawn over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BadgePosition {
+    TopLeft,
+    #[default]
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Size of the glyphs of [`FONT`], in font units.
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+/// Space between two glyphs, in font units.
+const GLYPH_SPACING: u32 = 1;
+/// Space above and below the text, in font units.
+const PADDING: u32 = 2;
+const BADGE_HEIGHT: u32 = GLYPH_HEIGHT + 2 * PADDING;
+/// Height of the badge relative to the icon, unless it has to be smaller to fit the text.
+const BADGE_SCALE: f32 = 0.5;
+
+/// A 5x7 bitmap font, every row is a mask with the leftmost pixel as the highest bit.
+#[rustfmt::skip]
+const FONT: [(char, [u8; GLYPH_HEIGHT as usize]); 13] = [
+    ('0', [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110]),
+    ('1', [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110]),
+    ('2', [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111]),
+    ('3', [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110]),
+    ('4', [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010]),
+    ('5', [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110]),
+    ('6', [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110]),
+    ('7', [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000]),
+    ('8', [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110]),
+    ('9', [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100]),
+    ('+', [0b00000, 0b00100, 0b00100, 0b11111, 0b00100, 0b00100, 0b00000]),
+    ('-', [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000]),
+    ('!', [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100]),
+];
+
+fn glyph(character: char) -> Result<&'static [u8; GLYPH_HEIGHT as usize], BadIcon> {
+    FONT.iter()
+        .find(|(c, _)| *c == character)
+        .map(|(_, rows)| rows)
+        .ok_or(BadIcon::UnsupportedCharacter { character })
+}
+
+impl RgbaIcon {
+    /// Draws `badge` over a corner, about half as high as the icon and narrower than it.
+    pub(crate) fn with_badge(&self, badge: &Badge) -> Result<RgbaIcon, BadIcon> {
+        let glyphs = badge
+            .text
+            .chars()
+            .map(glyph)
+            .collect::<Result<Vec<_>, _>>()?;
+        if self.width == 0 || self.height == 0 {
+            return Err(BadIcon::InvalidSize {
+                width: self.width,
+                height: self.height,
+            });
+        }
+
+        // the badge is laid out in font units, then scaled to `unit` pixels per font unit
+        let text_width =
+            (glyphs.len() as u32 * (GLYPH_WIDTH + GLYPH_SPACING)).saturating_sub(GLYPH_SPACING);
+        // one more unit on both sides, so the text stays clear of the rounded ends
+        let badge_width = (text_width + 2 * PADDING + 2).max(BADGE_HEIGHT);
+        let unit = (self.width.min(self.height) as f32 * BADGE_SCALE / BADGE_HEIGHT as f32)
+            .min(self.width as f32 / badge_width as f32);
+        let (width, height) = (badge_width as f32 * unit, BADGE_HEIGHT as f32 * unit);
+        let (left, top) = match badge.position {
+            BadgePosition::TopLeft => (0.0, 0.0),
+            BadgePosition::TopRight => (self.width as f32 - width, 0.0),
+            BadgePosition::BottomLeft => (0.0, self.height as f32 - height),
+            BadgePosition::BottomRight => (self.width as f32 - width, self.height as f32 - height),
+        };
+
+        let radius = BADGE_HEIGHT as f32 / 2.0;
+        let text_left = (badge_width - text_width) as f32 / 2.0;
+        let color_at = |x: f32, y: f32| {
+            // distance to the line between the centers of the rounded ends
+            let dx = x - x.clamp(radius, badge_width as f32 - radius);
+            let dy = y - radius;
+            if dx * dx + dy * dy > radius * radius {
+                return None;
+            }
+
+            let (x, y) = (x - text_left, y - PADDING as f32);
+            let mut color = badge.background;
+            if x >= 0.0 && y >= 0.0 {
+                let (column, row) = (x as u32 % (GLYPH_WIDTH + GLYPH_SPACING), y as u32);
+                let glyph = glyphs.get((x as u32 / (GLYPH_WIDTH + GLYPH_SPACING)) as usize);
+                if let Some(rows) = glyph.filter(|_| column < GLYPH_WIDTH && row < GLYPH_HEIGHT) {
+                    if rows[row as usize] >> (GLYPH_WIDTH - 1 - column) & 1 == 1 {
+                        blend_over(&mut color, &badge.foreground);
+                    }
+                }
+            }
+            Some(color)
+        };
+
+        let mut icon = self.clone();
+        icon.draw_shape(
+            left.floor().max(0.0) as u32..(left + width).ceil() as u32,
+            top.floor().max(0.0) as u32..(top + height).ceil() as u32,
+            |x, y| color_at((x - left) / unit, (y - top) / unit),
+        );
+        Ok(icon)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icon::tests::{assert_golden, source};
+    use crate::icon::PIXEL_SIZE;
+
+    #[test]
+    fn matches_golden_images() {
+        let icon = source();
+        assert_golden("badge", &icon.with_badge(&Badge::new("7")).unwrap());
+
+        let badge = Badge {
+            text: "99+".into(),
+            background: [37, 99, 235, 255],
+            foreground: [255, 255, 255, 255],
+            position: BadgePosition::BottomLeft,
+        };
+        let large = icon.resized(64, 64, super::super::Filter::Box).unwrap();
+        assert_golden("badge-wide", &large.with_badge(&badge).unwrap());
+    }
+
+    #[test]
+    fn draws_over_the_given_corner_only() {
+        // at this height, a font unit is a pixel
+        let blue = [0, 0, 255, 255];
+        let icon = RgbaIcon::from_rgba(blue.repeat(44 * 22), 44, 22).unwrap();
+        let pixel = |icon: &RgbaIcon, x: u32, y: u32| {
+            let i = (y * icon.width + x) as usize * PIXEL_SIZE;
+            icon.rgba[i..i + PIXEL_SIZE].to_vec()
+        };
+
+        let badge = Badge {
+            text: "1".into(),
+            background: [0, 255, 0, 255],
+            foreground: [255, 0, 0, 255],
+            position: BadgePosition::BottomRight,
+        };
+        let badged = icon.with_badge(&badge).unwrap();
+        // an 11x11 circle from 33,11 with the `1` from 36,13
+        assert_eq!(pixel(&badged, 36, 13), [0, 255, 0, 255]);
+        assert_eq!(pixel(&badged, 38, 13), [255, 0, 0, 255]);
+        assert_eq!(pixel(&badged, 37, 19), [255, 0, 0, 255]);
+        assert_ne!(pixel(&badged, 33, 16), blue);
+        assert_eq!(pixel(&badged, 33, 11), blue);
+        for y in 0..22 {
+            for x in 0..44 {
+                if x < 33 || y < 11 {
+                    assert_eq!(pixel(&badged, x, y), blue, "pixel at {x},{y}");
+                }
+            }
+        }
+
+        let dot = icon.with_badge(&Badge::default()).unwrap();
+        assert_eq!(pixel(&dot, 38, 5), [220, 38, 38, 255]);
+        assert_eq!(pixel(&dot, 38, 15), blue);
+
+        assert!(matches!(
+            icon.with_badge(&Badge::new("1a")),
+            Err(BadIcon::UnsupportedCharacter { character: 'a' })
+        ));
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Decoding of Apple `.icns` files.
+//!
+//! See <https://en.wikipedia.org/wiki/Apple_Icon_Image_format>.
+
+use super::{BadIcon, RgbaIcon, PNG_SIGNATURE};
+
+const MAGIC: &[u8; 4] = b"icns";
+const ELEMENT_HEADER_SIZE: usize = 8;
+
+/// The signatures of the JPEG 2000 file format and codestream.
+const JPEG_2000_SIGNATURES: [&[u8]; 2] = [
+    &[0, 0, 0, 0x0c, b'j', b'P', b' ', b' '],
+    &[0xff, 0x4f, 0xff, 0x51],
+];
+
+/// The RGB elements of the legacy formats, with their size and the element holding their alpha.
+const RGB_ELEMENTS: [(&[u8; 4], u32, &[u8; 4]); 4] = [
+    (b"is32", 16, b"s8mk"),
+    (b"il32", 32, b"l8mk"),
+    (b"ih32", 48, b"h8mk"),
+    (b"it32", 128, b"t8mk"),
+];
+
+/// The elements holding a PNG, JPEG 2000 or ARGB image, with their size.
+const IMAGE_ELEMENTS: [(&[u8; 4], u32); 15] = [
+    (b"icp4", 16),
+    (b"icp5", 32),
+    (b"icp6", 64),
+    (b"ic07", 128),
+    (b"ic08", 256),
+    (b"ic09", 512),
+    (b"ic10", 1024),
+    (b"ic11", 32),
+    (b"ic12", 64),
+    (b"ic13", 256),
+    (b"ic14", 512),
+    (b"ic04", 16),
+    (b"ic05", 32),
+    (b"icsb", 18),
+    (b"icsB", 36),
+];
+
+fn error(reason: impl Into<String>) -> BadIcon {
+    BadIcon::DecodingError {
+        format: "ICNS",
+        reason: reason.into(),
+    }
+}
+
+pub(crate) fn is_icns(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Decodes every image of an `.icns` file.
+///
+/// JPEG 2000 images can't be decoded and are skipped, an error is only returned
+/// if the file has no other image.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Vec<RgbaIcon>, BadIcon> {
+    let elements = elements(bytes)?;
+    let find = |kind: &[u8; 4]| elements.iter().find(|(k, _)| k == kind).map(|(_, d)| *d);
+
+    let mut images = Vec::new();
+    let mut skipped_jpeg_2000 = false;
+    for (kind, data) in &elements {
+        let name = String::from_utf8_lossy(kind);
+        if let Some((_, size, mask)) = RGB_ELEMENTS.iter().find(|(k, ..)| *k == kind) {
+            let alpha = find(mask);
+            let image = decode_rgb(data, *size, alpha, kind == b"it32")
+                .map_err(|reason| error(format!("`{name}`: {reason}")))?;
+            images.push(image);
+        } else if let Some((_, size)) = IMAGE_ELEMENTS.iter().find(|(k, _)| *k == kind) {
+            if data.starts_with(&PNG_SIGNATURE) {
+                images.push(RgbaIcon::from_png_bytes(data)?);
+            } else if let Some(data) = data.strip_prefix(b"ARGB") {
+                let image = decode_argb(data, *size)
+                    .map_err(|reason| error(format!("`{name}`: {reason}")))?;
+                images.push(image);
+            } else if JPEG_2000_SIGNATURES.iter().any(|s| data.starts_with(s)) {
+                skipped_jpeg_2000 = true;
+            } else {
+                return Err(error(format!("`{name}` has an unknown image format")));
+            }
+        }
+    }
+
+    if images.is_empty() {
+        return Err(if skipped_jpeg_2000 {
+            error("JPEG 2000 images aren't supported")
+        } else {
+            BadIcon::NoImages
+        });
+    }
+    Ok(images)
+}
+
+/// The type and data of an element.
+type Element<'a> = ([u8; 4], &'a [u8]);
+
+/// Splits the file into its elements.
+fn elements(bytes: &[u8]) -> Result<Vec<Element<'_>>, BadIcon> {
+    let header = bytes
+        .get(..ELEMENT_HEADER_SIZE)
+        .filter(|header| is_icns(header))
+        .ok_or_else(|| error("missing ICNS header"))?;
+    let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let bytes = bytes.get(..length).ok_or_else(|| error("truncated file"))?;
+
+    let mut elements = Vec::new();
+    let mut offset = ELEMENT_HEADER_SIZE;
+    while offset < bytes.len() {
+        let header = bytes
+            .get(offset..offset + ELEMENT_HEADER_SIZE)
+            .ok_or_else(|| error("truncated element header"))?;
+        let kind = [header[0], header[1], header[2], header[3]];
+        let length = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        let data = (length >= ELEMENT_HEADER_SIZE)
+            .then(|| bytes.get(offset + ELEMENT_HEADER_SIZE..offset + length))
+            .flatten()
+            .ok_or_else(|| {
+                error(format!(
+                    "element `{}` is truncated",
+                    String::from_utf8_lossy(&kind)
+                ))
+            })?;
+        elements.push((kind, data));
+        offset += length;
+    }
+    Ok(elements)
+}
+
+/// Decodes the run-length encoding used for the channels of RGB and ARGB elements,
+/// where each channel is stored after the other.
+fn unpack(data: &[u8], len: usize) -> Result<Vec<u8>, &'static str> {
+    let mut out = Vec::with_capacity(len);
+    let mut data = data.iter();
+    while out.len() < len {
+        let header = *data.next().ok_or("truncated image data")? as usize;
+        if header < 0x80 {
+            for _ in 0..=header {
+                out.push(*data.next().ok_or("truncated image data")?);
+            }
+        } else {
+            let value = *data.next().ok_or("truncated image data")?;
+            out.resize(out.len() + header - 125, value);
+        }
+    }
+    out.truncate(len);
+    Ok(out)
+}
+
+fn decode_rgb(
+    data: &[u8],
+    size: u32,
+    alpha: Option<&[u8]>,
+    padded: bool,
+) -> Result<RgbaIcon, &'static str> {
+    let pixels = (size * size) as usize;
+    let channels = if data.len() == pixels * 4 {
+        // small icons are sometimes stored uncompressed, as xRGB
+        let mut channels = vec![0; pixels * 3];
+        for (i, p) in data.chunks_exact(4).enumerate() {
+            channels[i] = p[1];
+            channels[pixels + i] = p[2];
+            channels[pixels * 2 + i] = p[3];
+        }
+        channels
+    } else {
+        let data = if padded {
+            data.get(4..).unwrap_or_default()
+        } else {
+            data
+        };
+        unpack(data, pixels * 3)?
+    };
+    let alpha = match alpha {
+        Some(alpha) => alpha.get(..pixels).ok_or("truncated mask")?,
+        None => &[],
+    };
+
+    let rgba = (0..pixels)
+        .flat_map(|i| {
+            [
+                channels[i],
+                channels[pixels + i],
+                channels[pixels * 2 + i],
+                alpha.get(i).copied().unwrap_or(255),
+            ]
+        })
+        .collect();
+    RgbaIcon::from_rgba(rgba, size, size).map_err(|_| "invalid image size")
+}
+
+fn decode_argb(data: &[u8], size: u32) -> Result<RgbaIcon, &'static str> {
+    let pixels = (size * size) as usize;
+    let channels = unpack(data, pixels * 4)?;
+    let rgba = (0..pixels)
+        .flat_map(|i| {
+            [
+                channels[pixels + i],
+                channels[pixels * 2 + i],
+                channels[pixels * 3 + i],
+                channels[i],
+            ]
+        })
+        .collect();
+    RgbaIcon::from_rgba(rgba, size, size).map_err(|_| "invalid image size")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn icns_file(elements: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (kind, data) in elements {
+            body.extend_from_slice(*kind);
+            body.extend_from_slice(&((data.len() + ELEMENT_HEADER_SIZE) as u32).to_be_bytes());
+            body.extend_from_slice(data);
+        }
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&((body.len() + ELEMENT_HEADER_SIZE) as u32).to_be_bytes());
+        file.extend(body);
+        file
+    }
+
+    #[test]
+    fn decodes_legacy_and_argb_elements() {
+        // 16x16: red, green and blue channels as runs, with a mask of literals
+        let rgb = [[0x80 + 125, 10], [0x80 + 125, 20], [0x80 + 125, 30]]
+            .iter()
+            .flat_map(|run| [*run, [0x80 + 125, run[1]]])
+            .flatten()
+            .collect::<Vec<_>>();
+        let mask = (0..=255).collect::<Vec<u8>>();
+
+        // 16x16 ARGB, alpha then each color channel
+        let argb = [
+            b"ARGB".to_vec(),
+            vec![0x80 + 125, 1, 0x80 + 123, 1, 1, 1, 2],
+        ]
+        .into_iter()
+        .chain([4, 5, 6].map(|value| [0x80 + 125, value].repeat(2)))
+        .flatten()
+        .collect::<Vec<_>>();
+
+        let file = icns_file(&[
+            (b"TOC ", vec![]),
+            (b"is32", rgb),
+            (b"s8mk", mask),
+            (b"ic04", argb),
+        ]);
+        let images = decode(&file).unwrap();
+
+        assert_eq!(images.len(), 2);
+        assert_eq!((images[0].width, images[0].height), (16, 16));
+        assert_eq!(&images[0].rgba[..8], [10, 20, 30, 0, 10, 20, 30, 1]);
+        assert_eq!(&images[0].rgba[1020..], [10, 20, 30, 255]);
+        assert_eq!(&images[1].rgba[..4], [4, 5, 6, 1]);
+        assert_eq!(&images[1].rgba[1016..], [4, 5, 6, 1, 4, 5, 6, 2]);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let mut truncated = icns_file(&[(b"is32", vec![0x80 + 125, 0])]);
+        assert!(matches!(
+            decode(&truncated),
+            Err(BadIcon::DecodingError { format: "ICNS", reason }) if reason == "`is32`: truncated image data"
+        ));
+
+        truncated.truncate(truncated.len() - 1);
+        assert!(matches!(
+            decode(&truncated),
+            Err(BadIcon::DecodingError { format: "ICNS", reason }) if reason == "truncated file"
+        ));
+
+        let jpeg_2000 = icns_file(&[(b"ic08", JPEG_2000_SIGNATURES[1].to_vec())]);
+        assert!(matches!(
+            decode(&jpeg_2000),
+            Err(BadIcon::DecodingError { format: "ICNS", reason }) if reason.contains("JPEG 2000")
+        ));
+        assert!(matches!(decode(&icns_file(&[])), Err(BadIcon::NoImages)));
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Decoding of Windows `.ico` files.
+//!
+//! See <https://learn.microsoft.com/en-us/previous-versions/ms997538(v=msdn.10)>.
+
+use super::{BadIcon, RgbaIcon, PNG_SIGNATURE};
+
+const HEADER: [u8; 4] = [0, 0, 1, 0];
+const HEADER_SIZE: usize = 6;
+const ENTRY_SIZE: usize = 16;
+
+/// `BI_RGB`, uncompressed pixels.
+const BI_RGB: u32 = 0;
+/// `BI_BITFIELDS`, 32-bit pixels with color masks after the header.
+const BI_BITFIELDS: u32 = 3;
+
+fn error(reason: impl Into<String>) -> BadIcon {
+    BadIcon::DecodingError {
+        format: "ICO",
+        reason: reason.into(),
+    }
+}
+
+pub(crate) fn is_ico(bytes: &[u8]) -> bool {
+    bytes.starts_with(&HEADER)
+}
+
+/// Decodes every image of an `.ico` file.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Vec<RgbaIcon>, BadIcon> {
+    if !is_ico(bytes) {
+        return Err(error("missing ICO header"));
+    }
+    let count = bytes
+        .get(4..HEADER_SIZE)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+        .ok_or_else(|| error("truncated header"))?;
+
+    (0..count)
+        .map(|i| {
+            let start = HEADER_SIZE + i * ENTRY_SIZE;
+            let entry = bytes
+                .get(start..start + ENTRY_SIZE)
+                .ok_or_else(|| error("truncated directory"))?;
+            let size = u32_at(entry, 8).unwrap_or_default() as usize;
+            let offset = u32_at(entry, 12).unwrap_or_default() as usize;
+            let data = offset
+                .checked_add(size)
+                .and_then(|end| bytes.get(offset..end))
+                .ok_or_else(|| error(format!("image {i} is out of bounds")))?;
+
+            if data.starts_with(&PNG_SIGNATURE) {
+                RgbaIcon::from_png_bytes(data)
+            } else {
+                decode_dib(data).map_err(|reason| error(format!("image {i}: {reason}")))
+            }
+        })
+        .collect()
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    let bytes = data.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Decodes a device independent bitmap as stored in icons: a `BITMAPINFOHEADER` with twice
+/// the image height, the color table, the bottom-up XOR bitmap and then the 1-bit AND mask.
+fn decode_dib(data: &[u8]) -> Result<RgbaIcon, &'static str> {
+    let header_size = u32_at(data, 0).ok_or("truncated bitmap header")? as usize;
+    if header_size < 40 {
+        return Err("unsupported bitmap header");
+    }
+    let width = u32_at(data, 4).ok_or("truncated bitmap header")? as i32;
+    let height = u32_at(data, 8).ok_or("truncated bitmap header")? as i32 / 2;
+    let bit_count = u16_at(data, 14).ok_or("truncated bitmap header")?;
+    let compression = u32_at(data, 16).ok_or("truncated bitmap header")?;
+    let colors_used = u32_at(data, 32).ok_or("truncated bitmap header")? as usize;
+
+    if !(1..=1024).contains(&width) || !(1..=1024).contains(&height) {
+        return Err("invalid bitmap size");
+    }
+    let (width, height) = (width as usize, height as usize);
+    let color_masks = match (compression, bit_count) {
+        (BI_RGB, _) => 0,
+        (BI_BITFIELDS, 32) if header_size == 40 => 12,
+        (BI_BITFIELDS, 32) => 0,
+        _ => return Err("compressed bitmaps aren't supported"),
+    };
+
+    let palette_offset = header_size + color_masks;
+    let palette_len = match bit_count {
+        1 | 4 | 8 if colors_used == 0 => 1 << bit_count,
+        1 | 4 | 8 => colors_used.min(256),
+        16 | 24 | 32 => 0,
+        _ => return Err("unsupported bit depth"),
+    };
+    let palette = data
+        .get(palette_offset..palette_offset + palette_len * 4)
+        .ok_or("truncated color table")?;
+
+    let stride = (width * bit_count as usize).div_ceil(32) * 4;
+    let xor_offset = palette_offset + palette.len();
+    let xor = data
+        .get(xor_offset..xor_offset + stride * height)
+        .ok_or("truncated bitmap")?;
+    // 32-bit bitmaps sometimes leave the mask out and only rely on their alpha channel
+    let mask_stride = width.div_ceil(32) * 4;
+    let mask_offset = xor_offset + xor.len();
+    let mask = data.get(mask_offset..mask_offset + mask_stride * height);
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        // rows are stored bottom-up
+        let row = &xor[(height - 1 - y) * stride..][..stride];
+        for x in 0..width {
+            let pixel = match bit_count {
+                1 | 4 | 8 => {
+                    let bits = bit_count as usize;
+                    let byte = row[x * bits / 8];
+                    let shift = 8 - bits - (x * bits % 8);
+                    let index = ((byte >> shift) as usize) & ((1 << bits) - 1);
+                    let color = palette
+                        .get(index * 4..index * 4 + 4)
+                        .ok_or("invalid color index")?;
+                    [color[2], color[1], color[0], 255]
+                }
+                16 => {
+                    let value = u16::from_le_bytes([row[x * 2], row[x * 2 + 1]]);
+                    let channel = |shift: u16| (((value >> shift) & 0x1f) as u8) << 3;
+                    [channel(10), channel(5), channel(0), 255]
+                }
+                24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 255],
+                _ => [row[x * 4 + 2], row[x * 4 + 1], row[x * 4], row[x * 4 + 3]],
+            };
+            rgba.extend_from_slice(&pixel);
+        }
+    }
+
+    let has_alpha = bit_count == 32 && rgba.chunks_exact(4).any(|p| p[3] != 0);
+    if let (false, Some(mask)) = (has_alpha, mask) {
+        for y in 0..height {
+            let row = &mask[(height - 1 - y) * mask_stride..][..mask_stride];
+            for x in 0..width {
+                let transparent = row[x / 8] & (0x80 >> (x % 8)) != 0;
+                rgba[(y * width + x) * 4 + 3] = if transparent { 0 } else { 255 };
+            }
+        }
+    } else if !has_alpha {
+        rgba.chunks_exact_mut(4).for_each(|p| p[3] = 255);
+    }
+
+    RgbaIcon::from_rgba(rgba, width as u32, height as u32).map_err(|_| "invalid bitmap size")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an `.ico` file out of `(width, height, data)` entries.
+    fn ico_file(images: &[(u8, u8, Vec<u8>)]) -> Vec<u8> {
+        let mut file = vec![0, 0, 1, 0, images.len() as u8, 0];
+        let mut offset = HEADER_SIZE + images.len() * ENTRY_SIZE;
+        for (width, height, data) in images {
+            file.extend_from_slice(&[*width, *height, 0, 0, 1, 0, 32, 0]);
+            file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            file.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += data.len();
+        }
+        for (_, _, data) in images {
+            file.extend_from_slice(data);
+        }
+        file
+    }
+
+    fn bitmap_header(width: u32, height: u32, bit_count: u16, colors: u32) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&40u32.to_le_bytes());
+        header.extend_from_slice(&width.to_le_bytes());
+        header.extend_from_slice(&(height * 2).to_le_bytes());
+        header.extend_from_slice(&1u16.to_le_bytes());
+        header.extend_from_slice(&bit_count.to_le_bytes());
+        header.extend_from_slice(&[0; 16]);
+        header.extend_from_slice(&colors.to_le_bytes());
+        header.extend_from_slice(&[0; 4]);
+        header
+    }
+
+    #[test]
+    fn decodes_bitmaps_with_masks_and_png() {
+        // 2x2, 32-bit with alpha, bottom row first
+        let mut argb = bitmap_header(2, 2, 32, 0);
+        argb.extend_from_slice(&[3, 2, 1, 255, 0, 0, 0, 0, 30, 20, 10, 128, 0, 0, 255, 255]);
+        argb.extend_from_slice(&[0; 8]);
+
+        // 2x1, 1-bit with a palette of black and white, the second pixel masked out
+        let mut mono = bitmap_header(2, 1, 1, 2);
+        mono.extend_from_slice(&[0, 0, 0, 0, 255, 255, 255, 0]);
+        mono.extend_from_slice(&[0b1000_0000, 0, 0, 0]);
+        mono.extend_from_slice(&[0b0100_0000, 0, 0, 0]);
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, 1, 1);
+        encoder.set_color(png::ColorType::Rgba);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&[9, 8, 7, 6]).unwrap();
+        writer.finish().unwrap();
+
+        let file = ico_file(&[(2, 2, argb), (2, 1, mono), (1, 1, png)]);
+        let images = decode(&file).unwrap();
+
+        assert_eq!((images[0].width, images[0].height), (2, 2));
+        assert_eq!(
+            images[0].rgba,
+            [10, 20, 30, 128, 255, 0, 0, 255, 1, 2, 3, 255, 0, 0, 0, 0]
+        );
+        assert_eq!(images[1].rgba, [255, 255, 255, 255, 0, 0, 0, 0]);
+        assert_eq!(images[2].rgba, [9, 8, 7, 6]);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let truncated = ico_file(&[(2, 2, bitmap_header(2, 2, 32, 0))]);
+        assert!(matches!(
+            decode(&truncated),
+            Err(BadIcon::DecodingError { format: "ICO", reason }) if reason == "image 0: truncated bitmap"
+        ));
+
+        let mut out_of_bounds = ico_file(&[(1, 1, vec![0; 4])]);
+        out_of_bounds.truncate(out_of_bounds.len() - 1);
+        assert!(matches!(
+            decode(&out_of_bounds),
+            Err(BadIcon::DecodingError { format: "ICO", reason }) if reason == "image 0 is out of bounds"
+        ));
+
+        assert!(decode(b"not an icon").is_err());
+    }
+}
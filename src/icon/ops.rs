@@ -0,0 +1,281 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Pixel operations behind [`Icon::resized`](super::Icon::resized) and friends.
+
+use super::{BadIcon, Pixel, RgbaIcon, PIXEL_SIZE};
+use crate::Rect;
+
+/// The filter used to resize an icon with [`Icon::resized`](super::Icon::resized).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Filter {
+    /// Picks the closest source pixel, keeping hard edges. Best for pixel art
+    /// scaled up by whole factors.
+    Nearest,
+    /// Interpolates between the four closest source pixels. Best for scaling up
+    /// or scaling down by less than half.
+    #[default]
+    Bilinear,
+    /// Averages every source pixel covered by a destination pixel. Best for scaling down
+    /// by larger factors, behaves like [`Filter::Nearest`] when scaling up.
+    Box,
+}
+
+impl Pixel {
+    fn from_bytes(bytes: &[u8]) -> Pixel {
+        Pixel {
+            r: bytes[0],
+            g: bytes[1],
+            b: bytes[2],
+            a: bytes[3],
+        }
+    }
+
+    fn to_bytes(self) -> [u8; PIXEL_SIZE] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// Luma from the ITU-R BT.601 coefficients.
+    fn luma(&self) -> u8 {
+        ((self.r as u32 * 299 + self.g as u32 * 587 + self.b as u32 * 114 + 500) / 1000) as u8
+    }
+}
+
+impl RgbaIcon {
+    fn from_pixels(pixels: impl IntoIterator<Item = Pixel>, width: u32, height: u32) -> RgbaIcon {
+        RgbaIcon {
+            rgba: pixels.into_iter().flat_map(Pixel::to_bytes).collect(),
+            width,
+            height,
+        }
+    }
+
+    fn pixels(&self) -> impl Iterator<Item = Pixel> + '_ {
+        self.rgba.chunks_exact(PIXEL_SIZE).map(Pixel::from_bytes)
+    }
+
+    fn pixel(&self, x: u32, y: u32) -> Pixel {
+        let i = (y as usize * self.width as usize + x as usize) * PIXEL_SIZE;
+        Pixel::from_bytes(&self.rgba[i..])
+    }
+
+    pub(crate) fn resized(
+        &self,
+        width: u32,
+        height: u32,
+        filter: Filter,
+    ) -> Result<RgbaIcon, BadIcon> {
+        if width == 0 || height == 0 || self.width == 0 || self.height == 0 {
+            return Err(BadIcon::InvalidSize { width, height });
+        }
+
+        Ok(match filter {
+            Filter::Nearest => {
+                // sample the source pixel under the center of each destination pixel
+                let source = |i: u32, len: u32, src_len: u32| {
+                    ((2 * i as u64 + 1) * src_len as u64 / (2 * len as u64)) as u32
+                };
+                let pixels = (0..height).flat_map(|y| {
+                    (0..width).map(move |x| {
+                        self.pixel(source(x, width, self.width), source(y, height, self.height))
+                    })
+                });
+                RgbaIcon::from_pixels(pixels, width, height)
+            }
+            Filter::Bilinear => self.bilinear(width, height),
+            Filter::Box => self.downscaled(width, height),
+        })
+    }
+
+    /// Interpolates with premultiplied alpha, so transparent pixels don't bleed their color.
+    fn bilinear(&self, width: u32, height: u32) -> RgbaIcon {
+        // the two source pixels around the center of a destination pixel, and the weight of the second
+        let sample = |i: u32, len: u32, src_len: u32| {
+            let center = ((i as f32 + 0.5) * src_len as f32 / len as f32 - 0.5).max(0.0);
+            let first = (center as u32).min(src_len - 1);
+            (first, (first + 1).min(src_len - 1), center - first as f32)
+        };
+        let premultiplied = |p: Pixel| {
+            let a = p.a as f32 / 255.0;
+            [p.r as f32 * a, p.g as f32 * a, p.b as f32 * a, p.a as f32]
+        };
+        let lerp = |a: [f32; 4], b: [f32; 4], t: f32| {
+            let mut out = [0.0; 4];
+            for i in 0..4 {
+                out[i] = a[i] + (b[i] - a[i]) * t;
+            }
+            out
+        };
+
+        let pixels = (0..height).flat_map(|y| {
+            let (y0, y1, ty) = sample(y, height, self.height);
+            (0..width).map(move |x| {
+                let (x0, x1, tx) = sample(x, width, self.width);
+                let top = lerp(
+                    premultiplied(self.pixel(x0, y0)),
+                    premultiplied(self.pixel(x1, y0)),
+                    tx,
+                );
+                let bottom = lerp(
+                    premultiplied(self.pixel(x0, y1)),
+                    premultiplied(self.pixel(x1, y1)),
+                    tx,
+                );
+                let [r, g, b, a] = lerp(top, bottom, ty);
+
+                let color = |c: f32| {
+                    if a > 0.0 {
+                        (c * 255.0 / a).round().min(255.0) as u8
+                    } else {
+                        0
+                    }
+                };
+                Pixel {
+                    r: color(r),
+                    g: color(g),
+                    b: color(b),
+                    a: a.round() as u8,
+                }
+            })
+        });
+        RgbaIcon::from_pixels(pixels, width, height)
+    }
+
+    /// Centers the icon in a transparent square as wide as its longest side.
+    pub(crate) fn padded_to_square(&self) -> RgbaIcon {
+        let size = self.width.max(self.height);
+        let (left, top) = ((size - self.width) / 2, (size - self.height) / 2);
+
+        let pixels = (0..size).flat_map(|y| {
+            (0..size).map(move |x| {
+                let inside =
+                    (left..left + self.width).contains(&x) && (top..top + self.height).contains(&y);
+                if inside {
+                    self.pixel(x - left, y - top)
+                } else {
+                    Pixel {
+                        r: 0,
+                        g: 0,
+                        b: 0,
+                        a: 0,
+                    }
+                }
+            })
+        });
+        RgbaIcon::from_pixels(pixels, size, size)
+    }
+
+    /// Keeps the part of the icon inside `rect`, which is clamped to the icon bounds.
+    pub(crate) fn cropped(&self, rect: Rect) -> Result<RgbaIcon, BadIcon> {
+        let clamp = |start: f64, len: u32, max: u32| {
+            let start = start.floor();
+            let end = (start + len as f64).clamp(0.0, max as f64) as u32;
+            let start = start.clamp(0.0, max as f64) as u32;
+            (start, end - start)
+        };
+        let (x, width) = clamp(rect.position.x, rect.size.width, self.width);
+        let (y, height) = clamp(rect.position.y, rect.size.height, self.height);
+        if width == 0 || height == 0 {
+            return Err(BadIcon::InvalidSize { width, height });
+        }
+
+        let pixels =
+            (y..y + height).flat_map(|py| (x..x + width).map(move |px| self.pixel(px, py)));
+        Ok(RgbaIcon::from_pixels(pixels, width, height))
+    }
+
+    /// Paints every pixel with `color`, keeping the shape of the icon through its alpha.
+    pub(crate) fn tinted(&self, color: [u8; 4]) -> RgbaIcon {
+        let pixels = self.pixels().map(|p| Pixel {
+            r: color[0],
+            g: color[1],
+            b: color[2],
+            a: ((p.a as u32 * color[3] as u32 + 127) / 255) as u8,
+        });
+        RgbaIcon::from_pixels(pixels, self.width, self.height)
+    }
+
+    pub(crate) fn grayscale(&self) -> RgbaIcon {
+        let pixels = self.pixels().map(|p| {
+            let luma = p.luma();
+            Pixel {
+                r: luma,
+                g: luma,
+                b: luma,
+                a: p.a,
+            }
+        });
+        RgbaIcon::from_pixels(pixels, self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dpi::{PhysicalPosition, PhysicalSize};
+    use crate::icon::tests::{assert_golden, source};
+
+    fn rect(x: f64, y: f64, width: u32, height: u32) -> Rect {
+        Rect {
+            position: PhysicalPosition::new(x, y),
+            size: PhysicalSize::new(width, height),
+        }
+    }
+
+    #[test]
+    fn matches_golden_images() {
+        let icon = source();
+        let logo = icon.cropped(rect(4.0, 8.0, 24, 14)).unwrap();
+
+        assert_golden("nearest", &icon.resized(22, 22, Filter::Nearest).unwrap());
+        assert_golden("bilinear", &icon.resized(22, 22, Filter::Bilinear).unwrap());
+        assert_golden(
+            "bilinear-up",
+            &logo.resized(48, 28, Filter::Bilinear).unwrap(),
+        );
+        assert_golden("box", &icon.resized(22, 22, Filter::Box).unwrap());
+        assert_golden("cropped", &logo);
+        assert_golden("padded", &logo.padded_to_square());
+        assert_golden("tinted", &icon.tinted([255, 255, 255, 200]));
+        assert_golden("grayscale", &icon.grayscale());
+    }
+
+    #[test]
+    fn pixel_exact_operations() {
+        let icon = RgbaIcon::from_rgba(
+            [[255, 0, 0, 255], [0, 255, 0, 128], [0, 0, 255, 0]].concat(),
+            3,
+            1,
+        )
+        .unwrap();
+
+        let padded = icon.padded_to_square();
+        assert_eq!((padded.width, padded.height), (3, 3));
+        assert_eq!(padded.rgba[..12], [0; 12]);
+        assert_eq!(padded.rgba[12..24], icon.rgba);
+
+        let cropped = icon.cropped(rect(-1.0, 0.0, 3, 5)).unwrap();
+        assert_eq!((cropped.width, cropped.height), (2, 1));
+        assert_eq!(cropped.rgba, icon.rgba[..8]);
+        assert!(matches!(
+            icon.cropped(rect(3.0, 0.0, 1, 1)),
+            Err(BadIcon::InvalidSize { .. })
+        ));
+
+        assert_eq!(
+            icon.tinted([10, 20, 30, 128]).rgba,
+            [[10, 20, 30, 128], [10, 20, 30, 64], [10, 20, 30, 0]].concat()
+        );
+        assert_eq!(
+            icon.grayscale().rgba,
+            [[76, 76, 76, 255], [150, 150, 150, 128], [29, 29, 29, 0]].concat()
+        );
+
+        let doubled = icon.resized(6, 2, Filter::Nearest).unwrap();
+        assert_eq!(doubled.rgba[..24], doubled.rgba[24..]);
+        assert_eq!(doubled.rgba[..8], [[255, 0, 0, 255]; 2].concat());
+        assert!(icon.resized(0, 1, Filter::Bilinear).is_err());
+    }
+}
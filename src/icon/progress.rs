@@ -0,0 +1,188 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Progress indicators drawn by [`Icon::with_progress`](super::Icon::with_progress).
+
+use std::f32::consts::{PI, TAU};
+
+use super::{RgbaIcon, SAMPLES};
+
+/// The progress of a long task, drawn over an icon.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Progress {
+    /// The done part of the task, from `0.0` to `1.0`. Other values are clamped.
+    pub value: f32,
+    pub style: ProgressStyle,
+    /// The RGBA color of the done part, the part left is drawn with a third of its alpha.
+    pub color: [u8; 4],
+}
+
+impl Progress {
+    /// Creates a blue progress ring.
+    pub fn new(value: f32) -> Self {
+        Self {
+            value,
+            ..Default::default()
+        }
+    }
+
+    fn clamped_value(&self) -> f32 {
+        if self.value.is_nan() {
+            0.0
+        } else {
+            self.value.clamp(0.0, 1.0)
+        }
+    }
+
+    /// Rounds the value to the steps that can be told apart when drawn on an icon
+    /// of `size` pixels, so values that would be drawn the same compare equal.
+    pub(crate) fn quantized(&self, size: u32) -> Progress {
+        // length of the indicator when full, in samples
+        let length = match self.style {
+            ProgressStyle::Ring => PI * size as f32,
+            ProgressStyle::Bar => size as f32,
+        };
+        let steps = (length * SAMPLES as f32).ceil().max(1.0);
+        Progress {
+            value: (self.clamped_value() * steps).round() / steps,
+            ..*self
+        }
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            value: 0.0,
+            style: ProgressStyle::default(),
+            color: [37, 99, 235, 255],
+        }
+    }
+}
+
+/// How a [`Progress`] is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProgressStyle {
+    /// A ring along the edges of the icon, filled clockwise from the top.
+    #[default]
+    Ring,
+    /// A bar along the bottom edge of the icon, filled from the left.
+    Bar,
+}
+
+/// Thickness of the ring or bar, relative to the icon size.
+const THICKNESS: f32 = 1.0 / 8.0;
+/// Alpha of the part left, relative to the alpha of the progress color.
+const TRACK_ALPHA: f32 = 1.0 / 3.0;
+
+impl RgbaIcon {
+    pub(crate) fn with_progress(&self, progress: &Progress) -> RgbaIcon {
+        let value = progress.clamped_value();
+        let (width, height) = (self.width as f32, self.height as f32);
+        let size = width.min(height);
+        let thickness = (size * THICKNESS).max(1.0);
+        let [r, g, b, a] = progress.color;
+        let track = [r, g, b, (a as f32 * TRACK_ALPHA).round() as u8];
+
+        let mut icon = self.clone();
+        match progress.style {
+            ProgressStyle::Ring => {
+                let (outer, inner) = (size / 2.0, size / 2.0 - thickness);
+                icon.draw_shape(0..self.width, 0..self.height, |x, y| {
+                    let (dx, dy) = (x - width / 2.0, y - height / 2.0);
+                    let distance = (dx * dx + dy * dy).sqrt();
+                    if distance > outer || distance < inner {
+                        return None;
+                    }
+                    // clockwise from the top, y points down
+                    let angle = dx.atan2(-dy).rem_euclid(TAU);
+                    Some(if angle < value * TAU {
+                        progress.color
+                    } else {
+                        track
+                    })
+                });
+            }
+            ProgressStyle::Bar => {
+                let top = height - thickness;
+                icon.draw_shape(0..self.width, top as u32..self.height, |x, y| {
+                    (y >= top).then_some(if x < value * width {
+                        progress.color
+                    } else {
+                        track
+                    })
+                });
+            }
+        }
+        icon
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icon::tests::{assert_golden, source};
+    use crate::icon::PIXEL_SIZE;
+
+    fn pixel(icon: &RgbaIcon, x: u32, y: u32) -> [u8; 4] {
+        let i = (y * icon.width + x) as usize * PIXEL_SIZE;
+        icon.rgba[i..i + PIXEL_SIZE].try_into().unwrap()
+    }
+
+    #[test]
+    fn matches_golden_images() {
+        let icon = source();
+        assert_golden("progress-ring", &icon.with_progress(&Progress::new(0.3)));
+
+        let bar = Progress {
+            value: 0.6,
+            style: ProgressStyle::Bar,
+            color: [22, 163, 74, 255],
+        };
+        assert_golden("progress-bar", &icon.with_progress(&bar));
+    }
+
+    #[test]
+    fn fills_the_done_part() {
+        let icon = RgbaIcon::from_rgba(vec![0; 16 * 16 * PIXEL_SIZE], 16, 16).unwrap();
+        let color = [255, 0, 0, 255];
+        let track = [255, 0, 0, 85];
+
+        let bar = Progress {
+            value: 0.5,
+            style: ProgressStyle::Bar,
+            color,
+        };
+        let bar = icon.with_progress(&bar);
+        // two pixels high along the bottom edge
+        assert_eq!(pixel(&bar, 7, 15), color);
+        assert_eq!(pixel(&bar, 8, 14), track);
+        assert_eq!(pixel(&bar, 8, 13), [0; 4]);
+
+        let ring = Progress {
+            value: 0.25,
+            style: ProgressStyle::Ring,
+            color,
+        };
+        let ring = icon.with_progress(&ring);
+        // two pixels thick along the edges, done from 12 to 3 o'clock
+        assert_eq!(pixel(&ring, 13, 3), color);
+        assert_eq!(pixel(&ring, 2, 3), track);
+        assert_eq!(pixel(&ring, 8, 8), [0; 4]);
+        assert_eq!(pixel(&ring, 0, 0), [0; 4]);
+    }
+
+    #[test]
+    fn quantizes_to_visible_steps() {
+        let quantized = |value: f32| Progress::new(value).quantized(16);
+
+        assert_eq!(quantized(0.5), quantized(0.5001));
+        assert_ne!(quantized(0.5), quantized(0.52));
+        assert_eq!(quantized(f32::NAN).value, 0.0);
+        assert_eq!(quantized(1.5).value, 1.0);
+        assert_eq!(quantized(0.5).value, 0.5);
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Icons rendered by [`Icon::from_text`](super::Icon::from_text).
+
+use ab_glyph::{point, Font, FontRef, GlyphId, GlyphImageFormat, PxScale};
+
+use super::{blend_over, BadIcon, Filter, RgbaIcon, PIXEL_SIZE};
+
+/// How [`Icon::from_text`](super::Icon::from_text) draws its text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextIconStyle<'a> {
+    /// The bytes of a TrueType or OpenType font.
+    pub font: &'a [u8],
+    /// The width and height of the icon in pixels.
+    pub size: u32,
+    /// The RGBA color of the text. Color glyphs of emoji fonts keep their own colors.
+    pub fg: [u8; 4],
+    /// The RGBA color of the background.
+    pub bg: [u8; 4],
+    /// The space between the text and the edges of the icon in pixels.
+    pub padding: u32,
+}
+
+impl<'a> TextIconStyle<'a> {
+    /// Creates a style drawing white text with `font` on a transparent 32x32 icon.
+    pub fn new(font: &'a [u8]) -> Self {
+        Self {
+            font,
+            size: 32,
+            fg: [255, 255, 255, 255],
+            bg: [0, 0, 0, 0],
+            padding: 2,
+        }
+    }
+}
+
+/// The most characters [`Icon::from_text`](super::Icon::from_text) draws.
+pub(crate) const MAX_CHARACTERS: usize = 4;
+
+/// A glyph with something to draw, placed on the baseline in font units.
+struct PlacedGlyph {
+    id: GlyphId,
+    /// Position of the glyph origin along the baseline.
+    x: f32,
+    /// Left, top, right and bottom edges of the drawn part, the y axis points up.
+    bounds: [f32; 4],
+    /// The color image of the glyph, which is drawn instead of its outline.
+    image: Option<RgbaIcon>,
+}
+
+/// Places the glyph `id` at `x`, returns `None` for glyphs without an outline or image.
+fn place(font: &FontRef, id: GlyphId, x: f32) -> Result<Option<PlacedGlyph>, BadIcon> {
+    // the largest strike, scaled down when drawn
+    let image = font
+        .glyph_raster_image2(id, u16::MAX)
+        .filter(|image| matches!(image.format, GlyphImageFormat::Png) && image.pixels_per_em > 0);
+    if let Some(image) = image {
+        let units = font.units_per_em().unwrap_or(1.0) / image.pixels_per_em as f32;
+        let decoded = RgbaIcon::from_png_bytes(image.data)?;
+        let (left, bottom) = (x + image.origin.x * units, image.origin.y * units);
+        return Ok(Some(PlacedGlyph {
+            id,
+            x,
+            bounds: [
+                left,
+                bottom + decoded.height as f32 * units,
+                left + decoded.width as f32 * units,
+                bottom,
+            ],
+            image: Some(decoded),
+        }));
+    }
+
+    Ok(font.outline(id).map(|outline| {
+        let (min, max) = (outline.bounds.min, outline.bounds.max);
+        PlacedGlyph {
+            id,
+            x,
+            // outline bounds have the top in `min`
+            bounds: [x + min.x, min.y, x + max.x, max.y],
+            image: None,
+        }
+    }))
+}
+
+impl RgbaIcon {
+    pub(crate) fn from_text(text: &str, style: &TextIconStyle) -> Result<RgbaIcon, BadIcon> {
+        let font = FontRef::try_from_slice(style.font).map_err(|e| BadIcon::DecodingError {
+            format: "font",
+            reason: e.to_string(),
+        })?;
+
+        // without shaping, joiners and variation selectors can only be skipped
+        let characters: Vec<char> = text
+            .chars()
+            .filter(|c| !matches!(c, '\u{200d}' | '\u{fe00}'..='\u{fe0f}'))
+            .collect();
+        if characters.is_empty() || characters.len() > MAX_CHARACTERS {
+            return Err(BadIcon::InvalidTextLength {
+                length: characters.len(),
+            });
+        }
+        // the RGBA bytes must be countable too
+        let pixels = style
+            .size
+            .checked_mul(style.size)
+            .filter(|pixels| *pixels != 0 && pixels.checked_mul(4).is_some())
+            .ok_or(BadIcon::InvalidSize {
+                width: style.size,
+                height: style.size,
+            })?;
+
+        let mut glyphs = Vec::with_capacity(characters.len());
+        let (mut pen, mut previous) = (0.0, None);
+        for character in characters {
+            let id = font.glyph_id(character);
+            if id.0 == 0 {
+                return Err(BadIcon::UnsupportedCharacter { character });
+            }
+            if let Some(previous) = previous {
+                pen += font.kern_unscaled(previous, id);
+            }
+            glyphs.extend(place(&font, id, pen)?);
+            pen += font.h_advance_unscaled(id);
+            previous = Some(id);
+        }
+
+        let mut icon = RgbaIcon {
+            rgba: style.bg.repeat(pixels as usize),
+            width: style.size,
+            height: style.size,
+        };
+        let ink = glyphs.iter().map(|glyph| glyph.bounds).reduce(|a, b| {
+            [
+                a[0].min(b[0]),
+                a[1].max(b[1]),
+                a[2].max(b[2]),
+                a[3].min(b[3]),
+            ]
+        });
+        // only blank characters like spaces
+        let Some([left, top, right, bottom]) = ink else {
+            return Ok(icon);
+        };
+
+        // fit the drawn part of the text inside the padding, centered
+        let size = style.size as f32;
+        let available = style.size.saturating_sub(2 * style.padding).max(1) as f32;
+        let scale = (available / (right - left)).min(available / (top - bottom));
+        let origin = (size - (right - left) * scale) / 2.0 - left * scale;
+        let baseline = (size - (top - bottom) * scale) / 2.0 + top * scale;
+
+        for glyph in &glyphs {
+            match &glyph.image {
+                Some(image) => {
+                    let [left, top, right, bottom] = glyph.bounds.map(|edge| edge * scale);
+                    let width = (right - left).round().max(1.0) as u32;
+                    let height = (top - bottom).round().max(1.0) as u32;
+                    let filter = if width * 2 < image.width {
+                        Filter::Box
+                    } else {
+                        Filter::Bilinear
+                    };
+                    let image = image.resized(width, height, filter)?;
+                    let (x0, y0) = ((origin + left).round(), (baseline - top).round());
+                    for (i, pixel) in image.rgba.chunks_exact(PIXEL_SIZE).enumerate() {
+                        let (x, y) = (i as u32 % width, i as u32 / width);
+                        icon.blend_at(x0 + x as f32, y0 + y as f32, pixel);
+                    }
+                }
+                None => {
+                    let scaled = glyph.id.with_scale_and_position(
+                        PxScale::from(scale * font.height_unscaled()),
+                        point(origin + glyph.x * scale, baseline),
+                    );
+                    let Some(outlined) = font.outline_glyph(scaled) else {
+                        continue;
+                    };
+                    let min = outlined.px_bounds().min;
+                    let [r, g, b, a] = style.fg;
+                    outlined.draw(|x, y, coverage| {
+                        let alpha = (a as f32 * coverage.min(1.0)).round() as u8;
+                        icon.blend_at(min.x + x as f32, min.y + y as f32, &[r, g, b, alpha]);
+                    });
+                }
+            }
+        }
+        Ok(icon)
+    }
+
+    /// Blends `color` over the pixel at `x`, `y` if it's inside the icon.
+    fn blend_at(&mut self, x: f32, y: f32, color: &[u8]) {
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * PIXEL_SIZE;
+        blend_over(&mut self.rgba[i..i + PIXEL_SIZE], color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::icon::tests::assert_golden;
+
+    /// A 16x16 smiley, the color image of the emoji in [`test_font`].
+    fn smiley() -> RgbaIcon {
+        let rgba = (0..16)
+            .flat_map(|y| {
+                (0..16).map(move |x| {
+                    let (dx, dy) = (x as f32 - 7.5, y as f32 - 7.5);
+                    if (x == 5 || x == 10) && (5..=6).contains(&y) {
+                        [40, 40, 40, 255]
+                    } else if dx * dx + dy * dy < 56.0 {
+                        [250, 200, 40, 255]
+                    } else {
+                        [0, 0, 0, 0]
+                    }
+                })
+            })
+            .flatten()
+            .collect();
+        RgbaIcon::from_rgba(rgba, 16, 16).unwrap()
+    }
+
+    /// A simple glyph outline with a single contour of on-curve points.
+    fn outline(points: &[(i16, i16)]) -> Vec<u8> {
+        let xs = points.iter().map(|p| p.0);
+        let ys = points.iter().map(|p| p.1);
+        let bounds = [
+            xs.clone().min().unwrap(),
+            ys.clone().min().unwrap(),
+            xs.max().unwrap(),
+            ys.max().unwrap(),
+        ];
+
+        let mut glyph = 1i16.to_be_bytes().to_vec();
+        glyph.extend(bounds.iter().flat_map(|v| v.to_be_bytes()));
+        glyph.extend((points.len() as u16 - 1).to_be_bytes());
+        // no instructions, then a flag per point: on curve with 16 bit coordinates
+        glyph.extend(0u16.to_be_bytes());
+        glyph.resize(glyph.len() + points.len(), 1);
+        for axis in [0, 1] {
+            let mut previous = 0;
+            for point in points {
+                let value = if axis == 0 { point.0 } else { point.1 };
+                glyph.extend((value - previous).to_be_bytes());
+                previous = value;
+            }
+        }
+        glyph
+    }
+
+    /// A font with an `I`, an `L`, a space and a smiley emoji stored as a PNG image
+    /// like in color emoji fonts, 1000 units per em.
+    fn test_font() -> Vec<u8> {
+        let be16 =
+            |values: &[i16]| -> Vec<u8> { values.iter().flat_map(|v| v.to_be_bytes()).collect() };
+        let be32 =
+            |values: &[u32]| -> Vec<u8> { values.iter().flat_map(|v| v.to_be_bytes()).collect() };
+
+        let glyphs = [
+            Vec::new(),
+            outline(&[(100, 0), (100, 700), (300, 700), (300, 0)]),
+            outline(&[
+                (100, 0),
+                (100, 700),
+                (250, 700),
+                (250, 150),
+                (500, 150),
+                (500, 0),
+            ]),
+            Vec::new(),
+            Vec::new(),
+        ];
+        let advances = [500, 400, 600, 300, 1000];
+        let (mut glyf, mut loca) = (Vec::new(), Vec::new());
+        for glyph in &glyphs {
+            loca.extend(be32(&[glyf.len() as u32]));
+            glyf.extend(glyph);
+        }
+        loca.extend(be32(&[glyf.len() as u32]));
+
+        let mut head = be32(&[0x0001_0000, 0x0001_0000, 0, 0x5F0F_3CF5]);
+        head.extend(be16(&[0, 1000]));
+        head.extend([0; 16]);
+        head.extend(be16(&[0, -200, 1000, 800, 0, 0, 0]));
+        // long offsets in `loca`
+        head.extend(be16(&[1, 0]));
+
+        let mut hhea = be32(&[0x0001_0000]);
+        hhea.extend(be16(&[
+            800, -200, 0, 1000, 0, 0, 1000, 1, 0, 0, 0, 0, 0, 0, 0, 5,
+        ]));
+        let hmtx: Vec<u8> = advances.iter().flat_map(|a| be16(&[*a, 0])).collect();
+        let mut maxp = be32(&[0x0000_5000]);
+        maxp.extend(be16(&[5]));
+
+        let groups: [[u32; 3]; 4] = [
+            [0x20, 0x20, 3],
+            [0x49, 0x49, 1],
+            [0x4C, 0x4C, 2],
+            [0x1F600, 0x1F600, 4],
+        ];
+        let mut cmap = be16(&[0, 1, 3, 10]);
+        cmap.extend(be32(&[12]));
+        cmap.extend(be16(&[12, 0]));
+        cmap.extend(be32(&[
+            16 + 12 * groups.len() as u32,
+            0,
+            groups.len() as u32,
+        ]));
+        cmap.extend(groups.iter().flat_map(|group| be32(group)));
+
+        // a 16 pixels per em strike, with the smiley 2 pixels below the baseline
+        let png = smiley().to_png();
+        let mut sbix = be16(&[1, 1]);
+        sbix.extend(be32(&[1, 12]));
+        sbix.extend(be16(&[16, 72]));
+        sbix.extend(be32(&[28, 28, 28, 28, 28, 28 + 8 + png.len() as u32]));
+        sbix.extend(be16(&[0, -2]));
+        sbix.extend(b"png ");
+        sbix.extend(png);
+
+        let tables = [
+            (b"cmap", cmap),
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"loca", loca),
+            (b"maxp", maxp),
+            (b"sbix", sbix),
+        ];
+        let mut font = be32(&[0x0001_0000]);
+        font.extend(be16(&[tables.len() as i16, 0, 0, 0]));
+        let mut data = Vec::new();
+        for (tag, table) in &tables {
+            let offset = 12 + 16 * tables.len() + data.len();
+            font.extend(*tag);
+            font.extend(be32(&[0, offset as u32, table.len() as u32]));
+            data.extend(table);
+            data.resize(data.len().next_multiple_of(4), 0);
+        }
+        font.extend(data);
+        font
+    }
+
+    fn alpha(icon: &RgbaIcon, x: u32, y: u32) -> u8 {
+        icon.rgba[(y * icon.width + x) as usize * PIXEL_SIZE + 3]
+    }
+
+    #[test]
+    fn matches_golden_images() {
+        let font = test_font();
+        let style = TextIconStyle {
+            font: &font,
+            size: 32,
+            fg: [255, 255, 255, 255],
+            bg: [30, 30, 30, 255],
+            padding: 3,
+        };
+        assert_golden("text", &RgbaIcon::from_text("IL", &style).unwrap());
+
+        let style = TextIconStyle {
+            size: 48,
+            ..TextIconStyle::new(&font)
+        };
+        assert_golden("text-emoji", &RgbaIcon::from_text("L😀", &style).unwrap());
+    }
+
+    #[test]
+    fn fits_text_inside_the_padding() {
+        let font = test_font();
+        let style = TextIconStyle {
+            size: 20,
+            ..TextIconStyle::new(&font)
+        };
+
+        // 200x700 units, so the height decides the scale
+        let icon = RgbaIcon::from_text("I", &style).unwrap();
+        assert_eq!((icon.width, icon.height), (20, 20));
+        assert!(alpha(&icon, 10, 2) > 250 && alpha(&icon, 10, 17) > 250);
+        assert_eq!(alpha(&icon, 10, 1), 0);
+        assert_eq!(alpha(&icon, 10, 18), 0);
+        assert_eq!(alpha(&icon, 6, 10), 0);
+        assert_eq!(
+            RgbaIcon::from_text("I\u{fe0f}", &style).unwrap(),
+            icon,
+            "variation selectors are skipped"
+        );
+
+        // color glyphs keep their colors
+        let emoji = RgbaIcon::from_text("😀", &style).unwrap();
+        let i = (10 * 20 + 10) * PIXEL_SIZE;
+        assert_eq!(emoji.rgba[i..i + PIXEL_SIZE], [250, 200, 40, 255]);
+
+        let blank = RgbaIcon::from_text(" ", &style).unwrap();
+        assert!(blank.rgba.iter().all(|c| *c == 0));
+    }
+
+    #[test]
+    fn rejects_invalid_text_and_fonts() {
+        let font = test_font();
+        let style = TextIconStyle::new(&font);
+
+        assert!(matches!(
+            RgbaIcon::from_text("", &style),
+            Err(BadIcon::InvalidTextLength { length: 0 })
+        ));
+        assert!(matches!(
+            RgbaIcon::from_text("IIIII", &style),
+            Err(BadIcon::InvalidTextLength { length: 5 })
+        ));
+        assert!(matches!(
+            RgbaIcon::from_text("IA", &style),
+            Err(BadIcon::UnsupportedCharacter { character: 'A' })
+        ));
+        for size in [0, 1 << 16] {
+            assert!(matches!(
+                RgbaIcon::from_text("I", &TextIconStyle { size, ..style }),
+                Err(BadIcon::InvalidSize { width, .. }) if width == size
+            ));
+        }
+        assert!(matches!(
+            RgbaIcon::from_text("I", &TextIconStyle::new(b"not a font")),
+            Err(BadIcon::DecodingError { format: "font", .. })
+        ));
+    }
+}
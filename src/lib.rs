@@ -19,18 +19,22 @@
 //!
 //! # Dependencies (Linux Only)
 //!
-//! On Linux, `gtk`, `libxdo` is used to make the predfined `Copy`, `Cut`, `Paste` and `SelectAll` menu items work and `libappindicator` or `libayatnat-appindicator` are used to create the tray icon, so make sure to install them on your system.
+//! On Linux, `gtk` is used for the tray menu and `libxdo` is used to make the predfined `Copy`, `Cut`, `Paste` and `SelectAll` menu items work, so make sure to install them on your system.
+//!
+//! The tray icon itself is exported over D-Bus using the [StatusNotifierItem](https://freedesktop.org/wiki/Specifications/StatusNotifierItem/) specification, so a session bus and a panel implementing it (KDE Plasma, XFCE, waybar, GNOME with the AppIndicator extension...) are needed for the icon to show up. The tray menu is exported alongside it using the `com.canonical.dbusmenu` interface and menu item clicks are reported through the gtk event loop, so it must keep running. GTK itself is only linked because muda builds its menus with it: the icon, tooltip, title, visibility and click and scroll events go over D-Bus from a background thread, without GTK being initialized. The gtk event loop is only needed for the tray menu, animated icons and following the GTK icon theme.
+//!
+//! On X11, when no StatusNotifierItem host is registered, the icon is docked in the XEmbed system tray instead, for panels like i3bar, dwm's systray or stalonetray. Icons move between the two as hosts start and stop, with a [`TrayIconEvent::Reregistered`] event when docked again.
 //!
 //! #### Arch Linux / Manjaro:
 //!
 //! ```sh
-//! pacman -S gtk3 xdotool libappindicator-gtk3 #or libayatana-appindicator
+//! pacman -S gtk3 xdotool
 //! ```
 //!
 //! #### Debian / Ubuntu:
 //!
 //! ```sh
-//! sudo apt install libgtk-3-dev libxdo-dev libappindicator3-dev #or libayatana-appindicator3-dev
+//! sudo apt install libgtk-3-dev libxdo-dev
 //! ```
 //!
 //! # Examples
@@ -121,25 +125,31 @@
 //! [tao]: https://docs.rs/tao
 
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     path::{Path, PathBuf},
-    rc::Rc,
+    rc::{Rc, Weak},
 };
 
 use counter::Counter;
 use crossbeam_channel::{unbounded, Receiver, Sender};
 use once_cell::sync::{Lazy, OnceCell};
 
+mod animation;
 mod counter;
 mod error;
 mod icon;
 mod platform_impl;
 mod tray_icon_id;
 
+pub use self::animation::{Animation, Repeat};
 pub use self::error::*;
-pub use self::icon::{BadIcon, Icon};
+#[cfg(feature = "text")]
+pub use self::icon::TextIconStyle;
+pub use self::icon::{BadIcon, Badge, BadgePosition, Filter, Icon, Progress, ProgressStyle};
 pub use self::tray_icon_id::TrayIconId;
 
+pub mod graph;
+
 /// Re-export of [muda](::muda) crate and used for tray context menu.
 pub mod menu {
     pub use muda::*;
@@ -151,17 +161,9 @@ static COUNTER: Counter = Counter::new();
 /// Attributes to use when creating a tray icon.
 pub struct TrayIconAttributes {
     /// Tray icon tooltip
-    ///
-    /// ## Platform-specific:
-    ///
-    /// - **Linux:** Unsupported.
     pub tooltip: Option<String>,
 
     /// Tray menu
-    ///
-    /// ## Platform-specific:
-    ///
-    /// - **Linux**: once a menu is set, it cannot be removed.
     pub menu: Option<Box<dyn menu::ContextMenu>>,
 
     /// Tray icon
@@ -172,7 +174,14 @@ pub struct TrayIconAttributes {
     ///     Setting an empty [`Menu`](crate::menu::Menu) is enough.
     pub icon: Option<Icon>,
 
+    /// Icon shown instead of [`icon`](Self::icon) while the tray icon is requesting attention.
+    ///
+    /// See [`TrayIcon::set_attention`].
+    pub attention_icon: Option<Icon>,
+
     /// Tray icon temp dir path. **Linux only**.
+    ///
+    /// When set, icons are also written to this dir as PNG files.
     pub temp_dir_path: Option<PathBuf>,
 
     /// Use the icon as a [template](https://developer.apple.com/documentation/appkit/nsimage/1520017-template?language=objc). **macOS only**.
@@ -192,6 +201,13 @@ pub struct TrayIconAttributes {
     ///   on the user's panel.  This may not be shown in all visualizations.
     /// - **Windows:** Unsupported.
     pub title: Option<String>,
+
+    /// Application identifier, hosts use it to remember the position and
+    /// visibility of the tray icon. **Linux only**.
+    pub app_id: Option<String>,
+
+    /// Tray icon category. **Linux only**.
+    pub category: Category,
 }
 
 impl Default for TrayIconAttributes {
@@ -200,10 +216,13 @@ impl Default for TrayIconAttributes {
             tooltip: None,
             menu: None,
             icon: None,
+            attention_icon: None,
             temp_dir_path: None,
             icon_is_template: false,
             menu_on_left_click: true,
             title: None,
+            app_id: None,
+            category: Category::default(),
         }
     }
 }
@@ -233,10 +252,6 @@ impl TrayIconBuilder {
     }
 
     /// Set the a menu for this tray icon.
-    ///
-    /// ## Platform-specific:
-    ///
-    /// - **Linux**: once a menu is set, it cannot be removed or replaced but you can change its content.
     pub fn with_menu(mut self, menu: Box<dyn menu::ContextMenu>) -> Self {
         self.attrs.menu = Some(menu);
         self
@@ -253,11 +268,15 @@ impl TrayIconBuilder {
         self
     }
 
-    /// Set a tooltip for this tray icon.
-    ///
-    /// ## Platform-specific:
+    /// Set the icon shown while this tray icon is requesting attention.
     ///
-    /// - **Linux:** Unsupported.
+    /// See [`TrayIcon::set_attention`].
+    pub fn with_attention_icon(mut self, icon: Icon) -> Self {
+        self.attrs.attention_icon = Some(icon);
+        self
+    }
+
+    /// Set a tooltip for this tray icon.
     pub fn with_tooltip<S: AsRef<str>>(mut self, s: S) -> Self {
         self.attrs.tooltip = Some(s.as_ref().to_string());
         self
@@ -278,10 +297,25 @@ impl TrayIconBuilder {
         self
     }
 
+    /// Set the application identifier. **Linux only**.
+    ///
+    /// Hosts use it to remember the position and visibility of the tray icon, so it should be
+    /// unique to the application and stay the same across runs.
+    pub fn with_app_id<S: AsRef<str>>(mut self, app_id: S) -> Self {
+        self.attrs.app_id = Some(app_id.as_ref().to_string());
+        self
+    }
+
+    /// Set the tray icon category. **Linux only**.
+    pub fn with_category(mut self, category: Category) -> Self {
+        self.attrs.category = category;
+        self
+    }
+
     /// Set tray icon temp dir path. **Linux only**.
     ///
-    /// On Linux, we need to write the icon to the disk and usually it will
-    /// be `$XDG_RUNTIME_DIR/tray-icon` or `$TEMP/tray-icon`.
+    /// On Linux, icons are sent to the panel in memory. When a temp dir path is set,
+    /// they are also written there as PNG files for panels that only load icons by name.
     pub fn with_temp_dir_path<P: AsRef<Path>>(mut self, s: P) -> Self {
         self.attrs.temp_dir_path = Some(s.as_ref().to_path_buf());
         self
@@ -318,6 +352,10 @@ impl TrayIconBuilder {
 pub struct TrayIcon {
     id: TrayIconId,
     tray: Rc<RefCell<platform_impl::TrayIcon>>,
+    /// Incremented when an animation is started or stopped, so frames
+    /// scheduled by a previous animation are dropped.
+    animation: Rc<Cell<u64>>,
+    decorations: Rc<RefCell<Decorations>>,
 }
 
 impl TrayIcon {
@@ -329,12 +367,15 @@ impl TrayIcon {
     ///   Setting an empty [`Menu`](crate::menu::Menu) is enough.
     pub fn new(attrs: TrayIconAttributes) -> Result<Self> {
         let id = TrayIconId(COUNTER.next().to_string());
+        let decorations = Decorations::new(attrs.icon.clone());
         Ok(Self {
             tray: Rc::new(RefCell::new(platform_impl::TrayIcon::new(
                 id.clone(),
                 attrs,
             )?)),
             id,
+            animation: Rc::default(),
+            decorations: Rc::new(RefCell::new(decorations)),
         })
     }
 
@@ -343,12 +384,15 @@ impl TrayIcon {
     /// See [`TrayIcon::new`] for more info.
     pub fn with_id<I: Into<TrayIconId>>(id: I, attrs: TrayIconAttributes) -> Result<Self> {
         let id = id.into();
+        let decorations = Decorations::new(attrs.icon.clone());
         Ok(Self {
             tray: Rc::new(RefCell::new(platform_impl::TrayIcon::new(
                 id.clone(),
                 attrs,
             )?)),
             id,
+            animation: Rc::default(),
+            decorations: Rc::new(RefCell::new(decorations)),
         })
     }
 
@@ -358,15 +402,86 @@ impl TrayIcon {
     }
 
     /// Set new tray icon. If `None` is provided, it will remove the icon.
+    ///
+    /// This stops the animation started with [`TrayIcon::set_animation`].
     pub fn set_icon(&self, icon: Option<Icon>) -> Result<()> {
+        self.animation.set(self.animation.get() + 1);
+        let icon = self.decorations.borrow_mut().set_icon(icon);
         self.tray.borrow_mut().set_icon(icon)
     }
 
-    /// Set new tray menu.
+    /// Plays `animation` in place of the icon, replacing the running animation.
+    ///
+    /// Frames are shown from the event loop of the thread the tray icon was created on,
+    /// so this doesn't need to be called repeatedly or from another thread. Once the animation
+    /// is over, its last frame stays until [`TrayIcon::stop_animation`] or
+    /// [`TrayIcon::set_icon`] is called. The attention icon is shown over the animation.
+    ///
+    /// Returns [`Error::InvalidAnimation`] if the animation has no frames, not one duration
+    /// per frame or a duration of zero.
     ///
     /// ## Platform-specific:
     ///
-    /// - **Linux**: once a menu is set it cannot be removed so `None` has no effect
+    /// - **Linux:** Frames are only sent as pixmaps and never written to the temp dir.
+    pub fn set_animation(&self, animation: Animation) -> Result<()> {
+        let player = animation::Player::new(animation)?;
+        let generation = self.animation.get() + 1;
+        self.animation.set(generation);
+        play_animation(
+            Rc::downgrade(&self.tray),
+            Rc::downgrade(&self.decorations),
+            Rc::downgrade(&self.animation),
+            generation,
+            player,
+        )
+    }
+
+    /// Stops the animation started with [`TrayIcon::set_animation`] and shows the icon again.
+    pub fn stop_animation(&self) -> Result<()> {
+        self.animation.set(self.animation.get() + 1);
+        let icon = self.decorations.borrow_mut().stop_animation();
+        self.tray.borrow_mut().set_icon(icon)
+    }
+
+    /// Shows `count` in a badge over the top-right corner of the icon, like an unread count.
+    /// Counts above 99 are shown as `99+`. If `None` is provided, it will remove the badge.
+    ///
+    /// The badge is drawn onto the icon and the frames of the animation, and drawn again
+    /// when they change. Use [`Icon::with_badge`] for other texts, colors or corners.
+    pub fn set_badge(&self, count: Option<u32>) -> Result<()> {
+        let badge = count.map(|count| match count {
+            0..=99 => Badge::new(count.to_string()),
+            _ => Badge::new("99+"),
+        });
+
+        let mut decorations = self.decorations.borrow_mut();
+        if decorations.badge == badge {
+            return Ok(());
+        }
+        decorations.badge = badge;
+        decorations.show(&mut self.tray.borrow_mut())
+    }
+
+    /// Draws `progress` over the icon, like a ring filling up during a long task.
+    /// If `None` is provided, it will remove the progress.
+    ///
+    /// The progress is drawn onto every size of the icon the platform uses and onto
+    /// the frames of the animation, under the badge. It is only drawn again when the change
+    /// is visible at the size of the icon, so this can be called on every update of the task.
+    pub fn set_progress(&self, progress: Option<Progress>) -> Result<()> {
+        let mut decorations = self.decorations.borrow_mut();
+        let changed = decorations.shown_size().is_some_and(|size| {
+            decorations.progress.map(|p| p.quantized(size)) != progress.map(|p| p.quantized(size))
+        });
+        // the exact value is kept for icons set later, which may be larger
+        decorations.progress = progress;
+        if !changed {
+            return Ok(());
+        }
+        decorations.show(&mut self.tray.borrow_mut())
+    }
+
+    /// Set new tray menu.
     pub fn set_menu(&self, menu: Option<Box<dyn menu::ContextMenu>>) {
         self.tray.borrow_mut().set_menu(menu)
     }
@@ -375,7 +490,8 @@ impl TrayIcon {
     ///
     /// ## Platform-specific:
     ///
-    /// - **Linux:** Unsupported
+    /// - **Linux:** The first line is shown as the title of the tooltip, and the
+    ///   other lines below it along with the icon.
     pub fn set_tooltip<S: AsRef<str>>(&self, tooltip: Option<S>) -> Result<()> {
         self.tray.borrow_mut().set_tooltip(tooltip)
     }
@@ -399,10 +515,47 @@ impl TrayIcon {
         self.tray.borrow_mut().set_visible(visible)
     }
 
+    /// Request or stop requesting the user's attention.
+    ///
+    /// While attention is requested, the attention icon is shown instead of the
+    /// regular icon. Without an attention icon, only hosts that highlight items on
+    /// their own will show a difference.
+    ///
+    /// ## Platform-specific:
+    ///
+    /// - **Linux:** Sets the item status to `NeedsAttention`, hosts decide how to show it.
+    ///   The XEmbed fallback only swaps the icon.
+    /// - **Windows / macOS:** Only swaps the icon.
+    pub fn set_attention(&self, attention: bool) -> Result<()> {
+        self.tray.borrow_mut().set_attention(attention)
+    }
+
+    /// Set the icon shown while this tray icon is requesting attention.
+    /// If `None` is provided, the regular icon is kept while requesting attention.
+    pub fn set_attention_icon(&self, icon: Option<Icon>) -> Result<()> {
+        self.tray.borrow_mut().set_attention_icon(icon)
+    }
+
+    /// Set a small icon drawn over the bottom-right corner of the tray icon,
+    /// like a status badge. If `None` is provided, it will remove the overlay.
+    ///
+    /// The overlay is drawn onto the icon and the attention icon, scaled down to at most
+    /// half their size.
+    ///
+    /// ## Platform-specific:
+    ///
+    /// - **Linux:** Few hosts draw the StatusNotifierItem `OverlayIconPixmap`, so it isn't
+    ///   used. Icons from the icon theme are sent as pixels while an overlay is set.
+    /// - **Windows:** Icons loaded from a file, a resource or a handle are shown
+    ///   without the overlay.
+    pub fn set_overlay_icon(&self, icon: Option<Icon>) -> Result<()> {
+        self.tray.borrow_mut().set_overlay_icon(icon)
+    }
+
     /// Sets the tray icon temp dir path. **Linux only**.
     ///
-    /// On Linux, we need to write the icon to the disk and usually it will
-    /// be `$XDG_RUNTIME_DIR/tray-icon` or `$TEMP/tray-icon`.
+    /// On Linux, icons are sent to the panel in memory. When a temp dir path is set,
+    /// they are also written there as PNG files for panels that only load icons by name.
     pub fn set_temp_dir_path<P: AsRef<Path>>(&self, path: Option<P>) {
         #[cfg(target_os = "linux")]
         self.tray.borrow_mut().set_temp_dir_path(path);
@@ -420,10 +573,13 @@ impl TrayIcon {
 
     pub fn set_icon_with_as_template(&self, icon: Option<Icon>, is_template: bool) -> Result<()> {
         #[cfg(target_os = "macos")]
-        return self
-            .tray
-            .borrow_mut()
-            .set_icon_with_as_template(icon, is_template);
+        {
+            self.animation.set(self.animation.get() + 1);
+            let icon = self.decorations.borrow_mut().set_icon(icon);
+            self.tray
+                .borrow_mut()
+                .set_icon_with_as_template(icon, is_template)
+        }
         #[cfg(not(target_os = "macos"))]
         {
             let _ = icon;
@@ -448,18 +604,153 @@ impl TrayIcon {
     ///
     /// ## Platform-specific:
     ///
-    /// - **Linux**: Unsupported.
+    /// - **Linux**: Only available when docked in an XEmbed system tray, StatusNotifierItem hosts don't report it.
     pub fn rect(&self) -> Option<Rect> {
         self.tray.borrow().rect()
     }
 }
 
+/// Shows the next frame of `player` and schedules the one after it, as long as the tray icon
+/// is alive and no other animation was started in the meantime.
+fn play_animation(
+    tray: Weak<RefCell<platform_impl::TrayIcon>>,
+    decorations: Weak<RefCell<Decorations>>,
+    current: Weak<Cell<u64>>,
+    generation: u64,
+    mut player: animation::Player,
+) -> Result<()> {
+    let Some((frame, duration)) = player.next_frame() else {
+        return Ok(());
+    };
+    let Some((tray_icon, tray_decorations)) = tray.upgrade().zip(decorations.upgrade()) else {
+        return Ok(());
+    };
+    let frame = tray_decorations.borrow_mut().set_frame(frame);
+    tray_icon.borrow_mut().set_animation_frame(frame)?;
+
+    platform_impl::run_after(duration, move || {
+        if current
+            .upgrade()
+            .is_some_and(|current| current.get() == generation)
+        {
+            if let Err(e) = play_animation(tray, decorations, current, generation, player) {
+                log::warn!("failed to show the next animation frame: {e}");
+            }
+        }
+    });
+    Ok(())
+}
+
+/// The icon and animation frame of a [`TrayIcon`] without its progress and badge,
+/// kept to draw them again when one of them changes.
+#[derive(Default)]
+struct Decorations {
+    icon: Option<Icon>,
+    frame: Option<Icon>,
+    /// The sizes of `icon` and `frame`, read once when they are set so progress
+    /// updates don't read the pixels back on Windows.
+    icon_size: u32,
+    frame_size: u32,
+    progress: Option<Progress>,
+    badge: Option<Badge>,
+}
+
+impl Decorations {
+    fn new(icon: Option<Icon>) -> Self {
+        Self {
+            icon_size: icon.as_ref().map_or(0, icon_size),
+            icon,
+            ..Default::default()
+        }
+    }
+
+    /// Stores the new icon, which also ends the animation, and returns it with the badge.
+    fn set_icon(&mut self, icon: Option<Icon>) -> Option<Icon> {
+        self.icon_size = icon.as_ref().map_or(0, icon_size);
+        self.icon = icon;
+        self.frame = None;
+        self.decorate(self.icon.as_ref(), self.icon_size)
+    }
+
+    /// Stores the new animation frame and returns it with the badge.
+    fn set_frame(&mut self, frame: Icon) -> Option<Icon> {
+        self.frame_size = icon_size(&frame);
+        let decorated = self.decorate(Some(&frame), self.frame_size);
+        self.frame = Some(frame);
+        decorated
+    }
+
+    /// Ends the animation and returns the icon with the badge, which may have
+    /// changed while only the frames were shown.
+    fn stop_animation(&mut self) -> Option<Icon> {
+        self.frame = None;
+        self.decorate(self.icon.as_ref(), self.icon_size)
+    }
+
+    /// Returns the size of the animation frame, or icon if there's no animation.
+    fn shown_size(&self) -> Option<u32> {
+        match (&self.frame, &self.icon) {
+            (Some(_), _) => Some(self.frame_size),
+            (None, Some(_)) => Some(self.icon_size),
+            (None, None) => None,
+        }
+    }
+
+    /// Shows the decorated animation frame, or icon if there's no animation.
+    fn show(&self, tray: &mut platform_impl::TrayIcon) -> Result<()> {
+        match &self.frame {
+            Some(frame) => tray.set_animation_frame(self.decorate(Some(frame), self.frame_size)),
+            None => tray.set_icon(self.decorate(self.icon.as_ref(), self.icon_size)),
+        }
+    }
+
+    /// Draws the progress and badge onto `icon`, which is kept as is if they can't be drawn.
+    /// The progress is drawn at `size`, the size of `icon`.
+    fn decorate(&self, icon: Option<&Icon>, size: u32) -> Option<Icon> {
+        let icon = icon?;
+        if self.progress.is_none() && self.badge.is_none() {
+            return Some(icon.clone());
+        }
+        let progress = self.progress.map(|p| p.quantized(size));
+        Some(
+            icon.decorated(progress.as_ref(), self.badge.as_ref())
+                .unwrap_or_else(|e| {
+                    log::warn!("failed to draw the progress or badge onto the tray icon: {e}");
+                    icon.clone()
+                }),
+        )
+    }
+}
+
+/// The size progress is drawn at on `icon`.
+fn icon_size(icon: &Icon) -> u32 {
+    icon.size().map_or(0, |(width, height)| width.max(height))
+}
+
+/// Returns whether a system tray host is available to show tray icons.
+///
+/// Useful to fall back to a regular window or a notification when tray icons
+/// would be invisible, changes are reported with [`TrayIconEvent::HostChanged`].
+///
+/// ## Platform-specific:
+///
+/// - **Windows / macOS**: Always [`HostStatus::Available`].
+/// - **Linux**: Available when a StatusNotifierItem host is registered with the
+///   `org.kde.StatusNotifierWatcher` or, on X11, when an XEmbed system tray is running.
+///   GNOME without the AppIndicator extension has neither.
+pub fn host_status() -> HostStatus {
+    platform_impl::host_status()
+}
+
 /// Describes a tray icon event.
 ///
 /// ## Platform-specific:
 ///
-/// - **Linux**: Unsupported. The event is not emmited even though the icon is shown
-///   and will still show a context menu on right click.
+/// - **Linux**: With StatusNotifierItem hosts, only [`TrayIconEvent::Click`] and [`TrayIconEvent::Scroll`] are emitted.
+///   Hosts only report completed clicks, so a [`MouseButtonState::Down`] and a [`MouseButtonState::Up`] event are emitted
+///   back to back, and the `rect` is always empty since the position of the icon on the panel is unknown.
+///   When docked in an XEmbed system tray, [`TrayIconEvent::Enter`], [`TrayIconEvent::Move`] and [`TrayIconEvent::Leave`]
+///   are emitted too and the `rect` is the real geometry of the icon.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(tag = "type"))]
@@ -517,6 +808,43 @@ pub enum TrayIconEvent {
         /// Position and size of the tray icon.
         rect: Rect,
     },
+    /// The mouse wheel was scrolled over the tray icon. **Linux Only**
+    #[cfg_attr(feature = "serde", serde(rename_all = "camelCase"))]
+    Scroll {
+        /// Id of the tray icon which triggered this event.
+        id: TrayIconId,
+        /// Amount of scrolling, as reported by the host.
+        ///
+        /// The sign gives the direction, positive values scroll down or right.
+        /// StatusNotifierItem hosts usually report `120` per wheel notch, while
+        /// XEmbed system trays report `1`.
+        delta: i32,
+        /// Whether the scrolling was vertical or horizontal.
+        orientation: ScrollOrientation,
+        /// Physical Position of this event.
+        position: dpi::PhysicalPosition<f64>,
+        /// Position and size of the tray icon.
+        rect: Rect,
+    },
+
+    /// The tray icon was added again after the system tray restarted,
+    /// with its current icon, tooltip, title, menu and visibility.
+    ///
+    /// ## Platform-specific:
+    ///
+    /// - **macOS**: Unsupported.
+    Reregistered {
+        /// Id of the tray icon which triggered this event.
+        id: TrayIconId,
+    },
+
+    /// The availability of a system tray host changed, see [`host_status`].
+    ///
+    /// Emitted once for all the tray icons, so its [`TrayIconEvent::id`] is empty.
+    HostChanged {
+        /// The new status.
+        status: HostStatus,
+    },
 }
 
 /// Describes the mouse button state.
@@ -548,6 +876,46 @@ impl Default for MouseButton {
     }
 }
 
+/// Describes the orientation of a [`TrayIconEvent::Scroll`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScrollOrientation {
+    Vertical,
+    Horizontal,
+}
+
+impl Default for ScrollOrientation {
+    fn default() -> Self {
+        Self::Vertical
+    }
+}
+
+/// Describes whether a system tray host is available to show tray icons.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HostStatus {
+    /// Tray icons are shown.
+    Available,
+    /// Tray icons are created but nothing shows them.
+    Missing,
+}
+
+/// Describes what kind of application a tray icon belongs to, hosts may use it
+/// to group or order tray icons. **Linux only**.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Category {
+    /// The status of a generic application, e.g. a media player or an updater.
+    #[default]
+    ApplicationStatus,
+    /// The status of a communication application, e.g. an instant messenger or an email client.
+    Communications,
+    /// A system service, e.g. a network manager or a disk indexer.
+    SystemServices,
+    /// The state of the hardware, e.g. the battery or the volume.
+    Hardware,
+}
+
 /// Describes a rectangle including position (x - y axis) and size.
 #[derive(Debug, PartialEq, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -574,13 +942,20 @@ static TRAY_EVENT_HANDLER: OnceCell<Option<TrayIconEventHandler>> = OnceCell::ne
 
 impl TrayIconEvent {
     /// Returns the id of the tray icon which triggered this event.
+    ///
+    /// [`TrayIconEvent::HostChanged`] concerns every tray icon and has an empty id.
     pub fn id(&self) -> &TrayIconId {
+        static NO_ID: TrayIconId = TrayIconId(String::new());
+
         match self {
             TrayIconEvent::Click { id, .. } => id,
             TrayIconEvent::DoubleClick { id, .. } => id,
             TrayIconEvent::Enter { id, .. } => id,
             TrayIconEvent::Move { id, .. } => id,
             TrayIconEvent::Leave { id, .. } => id,
+            TrayIconEvent::Scroll { id, .. } => id,
+            TrayIconEvent::Reregistered { id } => id,
+            TrayIconEvent::HostChanged { .. } => &NO_ID,
         }
     }
 
@@ -620,6 +995,42 @@ impl TrayIconEvent {
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn progress_is_drawn_for_the_icon_set_later() {
+        use super::*;
+        let icon = Icon::from_rgba(vec![255; 32 * 32 * 4], 32, 32).unwrap();
+        let progress = Progress::new(0.3);
+
+        let mut decorations = Decorations {
+            progress: Some(progress),
+            ..Default::default()
+        };
+        let shown = decorations.set_icon(Some(icon.clone())).unwrap();
+        assert_eq!(decorations.shown_size(), Some(32));
+
+        let expected = icon.decorated(Some(&progress.quantized(32)), None).unwrap();
+        let empty = icon.decorated(Some(&Progress::new(0.0)), None).unwrap();
+        assert_eq!(shown.to_rgba().unwrap(), expected.to_rgba().unwrap());
+        assert_ne!(shown.to_rgba().unwrap(), empty.to_rgba().unwrap());
+    }
+
+    #[test]
+    fn stopping_the_animation_keeps_the_badge() {
+        use super::*;
+        let icon = Icon::from_rgba(vec![255; 32 * 32 * 4], 32, 32).unwrap();
+        let frame = Icon::from_rgba(vec![0; 32 * 32 * 4], 32, 32).unwrap();
+
+        let mut decorations = Decorations::default();
+        decorations.set_icon(Some(icon.clone()));
+        decorations.set_frame(frame);
+        // set during the animation, so only drawn onto the frame until it stops
+        decorations.badge = Some(Badge::new("3"));
+
+        let shown = decorations.stop_animation().unwrap().to_rgba().unwrap();
+        let badged = icon.decorated(None, Some(&Badge::new("3"))).unwrap();
+        assert_eq!(shown, badged.to_rgba().unwrap());
+        assert_ne!(shown, icon.to_rgba().unwrap());
+    }
 
     #[cfg(feature = "serde")]
     #[test]
@@ -658,4 +1069,41 @@ mod tests {
             })
         )
     }
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_serializes_scroll() {
+        use super::*;
+        let event = TrayIconEvent::Scroll {
+            id: TrayIconId::new("id"),
+            delta: -120,
+            orientation: ScrollOrientation::Horizontal,
+            position: dpi::PhysicalPosition::default(),
+            rect: Rect::default(),
+        };
+
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "type": "Scroll",
+                "delta": -120,
+                "orientation": "Horizontal",
+                "id": "id",
+                "position": {
+                    "x": 0.0,
+                    "y": 0.0,
+                },
+                "rect": {
+                    "size": {
+                        "width": 0,
+                        "height": 0,
+                    },
+                    "position": {
+                        "x": 0.0,
+                        "y": 0.0,
+                    },
+                }
+            })
+        )
+    }
 }
@@ -0,0 +1,230 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Keeps track of whether a host is around to show the tray icons.
+//!
+//! Icons are visible when a StatusNotifierItem host is registered with the
+//! watcher or, on X11, when an XEmbed system tray is running.
+//!
+//! The watcher forgets about every item when it restarts, usually along with the
+//! panel, so items register again whenever a new watcher shows up.
+//!
+//! Hosts are followed once for the whole process, on a session connection and
+//! an X11 connection shared by every tray icon. Icons are docked in the XEmbed
+//! tray while no StatusNotifierItem host is registered, and removed from it when
+//! one shows up, like Windows adds them again when the taskbar is created.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use futures_util::{stream::select_all, StreamExt};
+use once_cell::sync::Lazy;
+use zbus::{fdo::DBusProxy, CacheProperties};
+
+use super::{
+    watcher::{StatusNotifierWatcherProxy, StatusNotifierWatcherProxyBlocking, WATCHER_NAME},
+    xembed::{self, XEmbedFallback},
+};
+use crate::{HostStatus, TrayIconEvent, TrayIconId};
+
+/// The hosts currently running.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Hosts {
+    /// A StatusNotifierItem host is registered with the watcher.
+    pub(crate) sni: bool,
+    /// An XEmbed system tray is running on the X11 display.
+    pub(crate) xembed: bool,
+}
+
+impl Hosts {
+    fn status(&self) -> HostStatus {
+        if self.sni || self.xembed {
+            HostStatus::Available
+        } else {
+            HostStatus::Missing
+        }
+    }
+}
+
+/// An item to register again with new watchers.
+struct Item {
+    id: TrayIconId,
+    name: String,
+    xembed: Arc<Mutex<XEmbedFallback>>,
+}
+
+struct Monitor {
+    hosts: Mutex<Hosts>,
+    /// Items of this process, by internal id.
+    items: Mutex<HashMap<u32, Item>>,
+}
+
+static MONITOR: Lazy<Monitor> = Lazy::new(|| {
+    let conn = zbus::blocking::Connection::session().ok();
+    let sni = conn.as_ref().is_some_and(has_sni_host);
+    let xembed = xembed::watch_tray(|xembed| MONITOR.update(|hosts| hosts.xembed = xembed));
+
+    if let Some(conn) = conn {
+        let conn = conn.inner().clone();
+        conn.executor()
+            .spawn(
+                {
+                    let conn = conn.clone();
+                    async move {
+                        let _ = watch_watcher(&conn).await;
+                    }
+                },
+                "tray-icon host watcher",
+            )
+            .detach();
+    }
+
+    Monitor {
+        hosts: Mutex::new(Hosts { sni, xembed }),
+        items: Mutex::new(HashMap::new()),
+    }
+});
+
+impl Monitor {
+    /// Applies `f` to the hosts, moving the items to the right backend and
+    /// emitting [`TrayIconEvent::HostChanged`] if the status changed.
+    fn update(&self, f: impl FnOnce(&mut Hosts)) {
+        let (old, new) = {
+            let mut hosts = self.hosts.lock().unwrap();
+            let old = *hosts;
+            f(&mut hosts);
+            (old, *hosts)
+        };
+        if old == new {
+            return;
+        }
+
+        let items = self
+            .items
+            .lock()
+            .unwrap()
+            .values()
+            .map(|item| (item.id.clone(), item.xembed.clone()))
+            .collect::<Vec<_>>();
+        for (id, xembed) in items {
+            if let Ok(true) = xembed.lock().unwrap().update(new) {
+                TrayIconEvent::send(TrayIconEvent::Reregistered { id });
+            }
+        }
+
+        if old.status() != new.status() {
+            TrayIconEvent::send(TrayIconEvent::HostChanged {
+                status: new.status(),
+            });
+        }
+    }
+}
+
+pub fn host_status() -> HostStatus {
+    hosts().status()
+}
+
+pub(crate) fn hosts() -> Hosts {
+    *MONITOR.hosts.lock().unwrap()
+}
+
+/// Keeps the item named `name` registered with new watchers and `xembed` docked
+/// when needed, until [`remove_item`] is called.
+pub(crate) fn add_item(
+    internal_id: u32,
+    id: TrayIconId,
+    name: String,
+    xembed: Arc<Mutex<XEmbedFallback>>,
+) {
+    MONITOR.items.lock().unwrap().insert(
+        internal_id,
+        Item {
+            id,
+            name,
+            xembed: xembed.clone(),
+        },
+    );
+
+    // the hosts may have changed before the item was added
+    let _ = xembed.lock().unwrap().update(hosts());
+}
+
+pub(crate) fn remove_item(internal_id: u32) {
+    MONITOR.items.lock().unwrap().remove(&internal_id);
+}
+
+fn has_sni_host(conn: &zbus::blocking::Connection) -> bool {
+    StatusNotifierWatcherProxyBlocking::builder(conn)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .and_then(|watcher| watcher.is_status_notifier_host_registered())
+        .unwrap_or(false)
+}
+
+enum Change {
+    /// The watcher name got a new owner, or lost it.
+    Watcher { started: bool },
+    /// A host was registered or unregistered with the watcher.
+    Host,
+}
+
+async fn watch_watcher(conn: &zbus::Connection) -> zbus::Result<()> {
+    let dbus = DBusProxy::new(conn).await?;
+    let watcher = StatusNotifierWatcherProxy::builder(conn)
+        .cache_properties(CacheProperties::No)
+        .build()
+        .await?;
+
+    // the watcher going away or coming back is reported as an owner change of its name
+    let mut changes = select_all([
+        dbus.receive_name_owner_changed_with_args(&[(0, WATCHER_NAME)])
+            .await?
+            .map(|signal| Change::Watcher {
+                started: signal.args().is_ok_and(|args| args.new_owner().is_some()),
+            })
+            .boxed(),
+        watcher
+            .receive_status_notifier_host_registered()
+            .await?
+            .map(|_| Change::Host)
+            .boxed(),
+        watcher
+            .receive_status_notifier_host_unregistered()
+            .await?
+            .map(|_| Change::Host)
+            .boxed(),
+    ]);
+
+    // a host may have been registered before the signals were subscribed to
+    let mut change = Some(Change::Host);
+    while let Some(current) = change {
+        // everything else is still exported as is, registering is enough to show up again
+        if matches!(current, Change::Watcher { started: true }) {
+            let items = MONITOR
+                .items
+                .lock()
+                .unwrap()
+                .values()
+                .map(|item| (item.id.clone(), item.name.clone()))
+                .collect::<Vec<_>>();
+            for (id, name) in items {
+                if watcher.register_status_notifier_item(&name).await.is_ok() {
+                    TrayIconEvent::send(TrayIconEvent::Reregistered { id });
+                }
+            }
+        }
+
+        let sni = watcher
+            .is_status_notifier_host_registered()
+            .await
+            .unwrap_or(false);
+        MONITOR.update(|hosts| hosts.sni = sni);
+
+        change = changes.next().await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,264 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+use std::{borrow::Cow, fs, io, path::Path};
+
+use super::{
+    item::Pixmap,
+    theme::{self, IconThemes},
+};
+#[cfg(feature = "svg")]
+use crate::icon::SvgIcon;
+use crate::icon::{best_for_size, BadIcon, RgbaIcon};
+
+/// Sizes panels commonly draw items at, smaller versions of the icon are sent
+/// for these so hosts pick a good match instead of scaling it down themselves.
+const PIXMAP_SIZES: [u32; 5] = [48, 32, 24, 22, 16];
+
+#[derive(Debug, Clone)]
+pub struct PlatformIcon {
+    /// The largest size of the icon.
+    pub(super) rgba: RgbaIcon,
+    /// Other sizes of the icon, largest first.
+    sizes: Vec<RgbaIcon>,
+    /// Set for icons from the icon theme, hosts look these up themselves.
+    pub(super) theme_name: Option<String>,
+    /// Set for vector icons, `rgba` is then rendered at twice the largest pixmap size.
+    #[cfg(feature = "svg")]
+    svg: Option<SvgIcon>,
+}
+
+impl PlatformIcon {
+    pub fn from_rgba(rgba: Vec<u8>, width: u32, height: u32) -> Result<Self, BadIcon> {
+        Ok(PlatformIcon {
+            rgba: RgbaIcon::from_rgba(rgba, width, height)?,
+            sizes: Vec::new(),
+            theme_name: None,
+            #[cfg(feature = "svg")]
+            svg: None,
+        })
+    }
+
+    /// `images` must not be empty and be sorted largest first.
+    pub fn from_sizes(mut images: Vec<RgbaIcon>) -> Result<Self, BadIcon> {
+        let rgba = images.remove(0);
+        Ok(PlatformIcon {
+            rgba,
+            sizes: images,
+            theme_name: None,
+            #[cfg(feature = "svg")]
+            svg: None,
+        })
+    }
+
+    #[cfg(feature = "svg")]
+    pub fn from_svg(svg: SvgIcon) -> Result<Self, BadIcon> {
+        Ok(PlatformIcon {
+            rgba: svg.rasterize(PIXMAP_SIZES[0] * 2),
+            sizes: Vec::new(),
+            theme_name: None,
+            svg: Some(svg),
+        })
+    }
+
+    /// Looks `name` up in the current icon theme, the pixels are loaded at the
+    /// largest of [`PIXMAP_SIZES`] for the XEmbed fallback and hosts that don't
+    /// support icon names.
+    ///
+    /// SVG icons can only be rendered with the `svg` feature, otherwise a transparent
+    /// pixel stands in for them and hosts are left to load the icon by name.
+    pub fn from_theme_name(name: &str) -> Result<Self, BadIcon> {
+        let path = theme::is_valid_name(name)
+            .then(|| {
+                IconThemes::system().find_icon(&theme::current_theme(), name, PIXMAP_SIZES[0], 1)
+            })
+            .flatten()
+            .ok_or_else(|| {
+                BadIcon::OsError(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("icon `{name}` not found in the icon theme"),
+                ))
+            })?;
+
+        let bytes = fs::read(&path).map_err(BadIcon::OsError)?;
+        let mut icon = if path.extension().is_some_and(|ext| ext == "svg") {
+            Self::from_theme_svg(&bytes)?
+        } else {
+            Self::from_sizes(vec![RgbaIcon::from_png_bytes(&bytes)?])?
+        };
+        icon.theme_name = Some(name.to_string());
+        Ok(icon)
+    }
+
+    #[cfg(feature = "svg")]
+    fn from_theme_svg(bytes: &[u8]) -> Result<Self, BadIcon> {
+        Self::from_svg(SvgIcon::from_bytes(bytes)?)
+    }
+
+    #[cfg(not(feature = "svg"))]
+    fn from_theme_svg(_bytes: &[u8]) -> Result<Self, BadIcon> {
+        Self::from_rgba(vec![0; 4], 1, 1)
+    }
+
+    pub fn rgba_icon(&self) -> Option<&RgbaIcon> {
+        Some(&self.rgba)
+    }
+
+    pub fn to_rgba_icon(&self) -> Result<Cow<'_, RgbaIcon>, BadIcon> {
+        Ok(Cow::Borrowed(&self.rgba))
+    }
+
+    /// Returns the image to draw at `size` pixels, which may need to be scaled down.
+    pub(super) fn image_for_size(&self, size: u32) -> Cow<'_, RgbaIcon> {
+        #[cfg(feature = "svg")]
+        if let Some(svg) = &self.svg {
+            return Cow::Owned(svg.rasterize(size));
+        }
+
+        let images = std::iter::once(&self.rgba).chain(&self.sizes);
+        Cow::Borrowed(best_for_size(images, size).unwrap_or(&self.rgba))
+    }
+
+    /// Returns the images sent to hosts as the `IconPixmap`, largest first: every size
+    /// of the icon, followed by downscaled versions for each of [`PIXMAP_SIZES`] smaller
+    /// than the largest size and missing from the icon.
+    ///
+    /// Vector icons are rendered at each of [`PIXMAP_SIZES`] and their HiDPI doubles instead.
+    pub fn exported_images(&self) -> Vec<Cow<'_, RgbaIcon>> {
+        #[cfg(feature = "svg")]
+        if let Some(svg) = &self.svg {
+            let mut sizes: Vec<u32> = PIXMAP_SIZES.iter().flat_map(|s| [s * 2, *s]).collect();
+            sizes.sort_unstable_by(|a, b| b.cmp(a));
+            sizes.dedup();
+            return sizes
+                .into_iter()
+                .map(|size| Cow::Owned(svg.rasterize(size)))
+                .collect();
+        }
+
+        let side = |icon: &RgbaIcon| icon.width.max(icon.height);
+        let images = std::iter::once(&self.rgba).chain(&self.sizes);
+
+        let mut exported: Vec<_> = images.clone().map(Cow::Borrowed).collect();
+        for size in PIXMAP_SIZES {
+            if size >= side(&self.rgba) || exported.iter().any(|icon| side(icon) == size) {
+                continue;
+            }
+
+            let icon = best_for_size(images.clone(), size).unwrap_or(&self.rgba);
+            let width = icon.width * size / side(icon);
+            let height = icon.height * size / side(icon);
+            exported.push(Cow::Owned(icon.downscaled(width, height)));
+        }
+
+        exported.sort_by_key(|icon| std::cmp::Reverse(side(icon)));
+        exported
+    }
+
+    /// Converts the icon to the `IconPixmap` format, see [`PlatformIcon::exported_images`].
+    pub fn to_pixmaps(&self) -> Vec<Pixmap> {
+        self.exported_images()
+            .iter()
+            .map(|icon| to_pixmap(icon))
+            .collect()
+    }
+
+    /// Writes the image best matching the largest of [`PIXMAP_SIZES`], hosts
+    /// scale it down to the panel size.
+    pub fn write_to_png(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        fs::write(path, self.image_for_size(PIXMAP_SIZES[0]).to_png())?;
+        Ok(())
+    }
+}
+
+/// Pixmaps are ARGB32 in network byte order, so each RGBA pixel becomes `[A, R, G, B]`.
+fn to_pixmap(icon: &RgbaIcon) -> Pixmap {
+    let data = icon
+        .rgba
+        .chunks_exact(4)
+        .flat_map(|p| [p[3], p[0], p[1], p[2]])
+        .collect();
+    (icon.width as i32, icon.height as i32, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pixmaps_are_argb_with_smaller_sizes() {
+        let rgba = [10, 20, 30, 255].repeat(32 * 16);
+        let pixmaps = PlatformIcon::from_rgba(rgba, 32, 16).unwrap().to_pixmaps();
+
+        let sizes: Vec<_> = pixmaps.iter().map(|(w, h, _)| (*w, *h)).collect();
+        assert_eq!(sizes, [(32, 16), (24, 12), (22, 11), (16, 8)]);
+        for (w, h, data) in pixmaps {
+            assert_eq!(data.len(), (w * h * 4) as usize);
+            assert_eq!(data[..4], [255, 10, 20, 30]);
+        }
+    }
+
+    #[test]
+    fn loads_svg_theme_icons() {
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg" width="16" height="16">
+            <rect width="16" height="16" fill="red"/></svg>"#;
+        let icon = PlatformIcon::from_theme_svg(svg).unwrap();
+
+        #[cfg(feature = "svg")]
+        assert_eq!(icon.rgba.rgba[..4], [255, 0, 0, 255]);
+        // hosts load it by name instead
+        #[cfg(not(feature = "svg"))]
+        assert_eq!(icon.to_pixmaps(), [(1, 1, vec![0; 4])]);
+    }
+
+    #[test]
+    fn every_size_is_exported() {
+        let icon = |size: u32| {
+            RgbaIcon::from_rgba(
+                [size as u8, 0, 0, 255].repeat((size * size) as usize),
+                size,
+                size,
+            )
+            .unwrap()
+        };
+        let icon = PlatformIcon::from_sizes(vec![icon(64), icon(22), icon(20)]).unwrap();
+
+        let pixmaps = icon.to_pixmaps();
+
+        let sizes: Vec<_> = pixmaps.iter().map(|(w, _, data)| (*w, data[1])).collect();
+        // missing sizes are scaled down from the closest larger image
+        assert_eq!(
+            sizes,
+            [
+                (64, 64),
+                (48, 64),
+                (32, 64),
+                (24, 64),
+                (22, 22),
+                (20, 20),
+                (16, 20)
+            ]
+        );
+    }
+
+    #[cfg(feature = "svg")]
+    #[test]
+    fn svg_is_rendered_at_each_pixmap_size() {
+        let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+            <rect width="10" height="10" fill="#ff0000"/>
+        </svg>"##;
+        let icon = PlatformIcon::from_svg(SvgIcon::from_bytes(svg).unwrap()).unwrap();
+
+        let pixmaps = icon.to_pixmaps();
+
+        let sizes: Vec<_> = pixmaps.iter().map(|(w, h, _)| (*w, *h)).collect();
+        assert_eq!(
+            sizes,
+            [96, 64, 48, 44, 32, 24, 22, 16].map(|size| (size, size))
+        );
+        for (_, _, data) in pixmaps {
+            assert!(data.chunks(4).all(|p| p == [255, 255, 0, 0]));
+        }
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! The `org.kde.StatusNotifierItem` interface exported for each tray icon.
+//!
+//! See <https://freedesktop.org/wiki/Specifications/StatusNotifierItem/StatusNotifierItem/>.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use zbus::{interface, zvariant::ObjectPath, SignalContext};
+
+use crate::{
+    dpi::PhysicalPosition, Category, MouseButton, MouseButtonState, Rect, ScrollOrientation,
+    TrayIconEvent, TrayIconId,
+};
+
+use super::menu::MENU_PATH;
+
+pub(crate) const ITEM_PATH: &str = "/StatusNotifierItem";
+
+/// An ARGB32 image in network byte order, as `(width, height, data)`.
+pub(crate) type Pixmap = (i32, i32, Vec<u8>);
+
+/// The `ToolTip` property, as `(icon name, icon pixmaps, title, description)`.
+pub(crate) type ToolTip = (String, Vec<Pixmap>, String, String);
+
+/// Describes the `Status` property of an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Status {
+    Passive,
+    Active,
+    NeedsAttention,
+}
+
+impl Status {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Status::Passive => "Passive",
+            Status::Active => "Active",
+            Status::NeedsAttention => "NeedsAttention",
+        }
+    }
+}
+
+/// Values exposed through the item properties.
+///
+/// This is shared between [`super::TrayIcon`], which updates it on the
+/// event loop thread, and the object server which reads it when the host asks.
+#[derive(Debug)]
+pub(crate) struct ItemState {
+    pub(crate) id: String,
+    pub(crate) category: Category,
+    pub(crate) title: String,
+    pub(crate) tooltip: String,
+    pub(crate) status: Status,
+    pub(crate) icon_name: String,
+    pub(crate) icon_theme_path: String,
+    pub(crate) icon_pixmap: Vec<Pixmap>,
+    pub(crate) attention_icon_name: String,
+    pub(crate) attention_icon_pixmap: Vec<Pixmap>,
+    pub(crate) has_menu: bool,
+}
+
+impl Default for ItemState {
+    fn default() -> Self {
+        Self {
+            id: "tray-icon tray app".to_string(),
+            category: Category::ApplicationStatus,
+            title: String::new(),
+            tooltip: String::new(),
+            status: Status::Active,
+            icon_name: String::new(),
+            icon_theme_path: String::new(),
+            icon_pixmap: Vec::new(),
+            attention_icon_name: String::new(),
+            attention_icon_pixmap: Vec::new(),
+            has_menu: false,
+        }
+    }
+}
+
+pub(crate) struct StatusNotifierItem {
+    id: TrayIconId,
+    internal_id: u32,
+    state: Arc<Mutex<ItemState>>,
+}
+
+impl StatusNotifierItem {
+    pub(crate) fn new(id: TrayIconId, internal_id: u32, state: Arc<Mutex<ItemState>>) -> Self {
+        Self {
+            id,
+            internal_id,
+            state,
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, ItemState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Hosts only report completed clicks, so emit both the press and the release.
+    fn send_click(&self, button: MouseButton, x: i32, y: i32) {
+        let position = PhysicalPosition::new(x as f64, y as f64);
+        for button_state in [MouseButtonState::Down, MouseButtonState::Up] {
+            TrayIconEvent::send(TrayIconEvent::Click {
+                id: self.id.clone(),
+                position,
+                rect: Rect::default(),
+                button,
+                button_state,
+            });
+        }
+    }
+}
+
+#[interface(name = "org.kde.StatusNotifierItem")]
+impl StatusNotifierItem {
+    /// Primary activation, usually a left click on the item.
+    fn activate(&self, x: i32, y: i32) {
+        self.send_click(MouseButton::Left, x, y);
+    }
+
+    /// Secondary activation, usually a middle click on the item.
+    fn secondary_activate(&self, x: i32, y: i32) {
+        self.send_click(MouseButton::Middle, x, y);
+    }
+
+    /// The mouse wheel was used over the item, `orientation` is either `vertical` or `horizontal`.
+    ///
+    /// Hosts pass on Qt style wheel deltas, positive when scrolling up or left,
+    /// so the sign is flipped to match [`TrayIconEvent::Scroll`].
+    fn scroll(&self, delta: i32, orientation: &str) {
+        let orientation = if orientation.eq_ignore_ascii_case("horizontal") {
+            ScrollOrientation::Horizontal
+        } else {
+            ScrollOrientation::Vertical
+        };
+
+        TrayIconEvent::send(TrayIconEvent::Scroll {
+            id: self.id.clone(),
+            delta: delta.saturating_neg(),
+            orientation,
+            position: PhysicalPosition::default(),
+            rect: Rect::default(),
+        });
+    }
+
+    /// Asks the item to show its own context menu, hosts call this on right click
+    /// when the item doesn't export a dbusmenu.
+    fn context_menu(&self, x: i32, y: i32) {
+        self.send_click(MouseButton::Right, x, y);
+
+        super::menu::show(self.internal_id);
+    }
+
+    #[zbus(property)]
+    fn category(&self) -> &str {
+        match self.state().category {
+            Category::ApplicationStatus => "ApplicationStatus",
+            Category::Communications => "Communications",
+            Category::SystemServices => "SystemServices",
+            Category::Hardware => "Hardware",
+        }
+    }
+
+    #[zbus(property)]
+    fn id(&self) -> String {
+        self.state().id.clone()
+    }
+
+    #[zbus(property)]
+    fn title(&self) -> String {
+        self.state().title.clone()
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> &str {
+        self.state().status.as_str()
+    }
+
+    #[zbus(property)]
+    fn window_id(&self) -> i32 {
+        0
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> String {
+        self.state().icon_theme_path.clone()
+    }
+
+    #[zbus(property)]
+    fn icon_name(&self) -> String {
+        self.state().icon_name.clone()
+    }
+
+    /// Hosts prefer `IconName` when it is set and fall back to these otherwise.
+    #[zbus(property)]
+    fn icon_pixmap(&self) -> Vec<Pixmap> {
+        self.state().icon_pixmap.clone()
+    }
+
+    #[zbus(property)]
+    fn attention_icon_name(&self) -> String {
+        self.state().attention_icon_name.clone()
+    }
+
+    /// Shown instead of the icon while the status is `NeedsAttention`.
+    #[zbus(property)]
+    fn attention_icon_pixmap(&self) -> Vec<Pixmap> {
+        self.state().attention_icon_pixmap.clone()
+    }
+
+    /// Overlays are drawn onto the icon pixmaps instead, since most hosts ignore these.
+    #[zbus(property)]
+    fn overlay_icon_name(&self) -> &str {
+        ""
+    }
+
+    #[zbus(property)]
+    fn overlay_icon_pixmap(&self) -> Vec<Pixmap> {
+        Vec::new()
+    }
+
+    /// Hosts show the title in bold with the description below it, so the first
+    /// line of the tooltip text is the title and the other lines the description.
+    #[zbus(property)]
+    fn tool_tip(&self) -> ToolTip {
+        let state = self.state();
+        let (title, description) = state
+            .tooltip
+            .split_once('\n')
+            .unwrap_or((&state.tooltip, ""));
+        (
+            state.icon_name.clone(),
+            state.icon_pixmap.clone(),
+            title.to_string(),
+            description.to_string(),
+        )
+    }
+
+    #[zbus(property)]
+    fn item_is_menu(&self) -> bool {
+        false
+    }
+
+    /// Hosts show the exported menu on right click instead of calling
+    /// `ContextMenu`, so it is only advertised when a menu is set.
+    #[zbus(property)]
+    fn menu(&self) -> ObjectPath<'_> {
+        if self.state().has_menu {
+            ObjectPath::from_static_str_unchecked(MENU_PATH)
+        } else {
+            ObjectPath::from_static_str_unchecked("/NO_DBUSMENU")
+        }
+    }
+
+    /// Ayatana extension used by GNOME and Unity to show a text label next to the icon.
+    #[zbus(property)]
+    fn x_ayatana_label(&self) -> String {
+        self.state().title.clone()
+    }
+
+    #[zbus(property)]
+    fn x_ayatana_label_guide(&self) -> &str {
+        ""
+    }
+
+    #[zbus(signal)]
+    pub(crate) async fn new_title(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub(crate) async fn new_icon(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub(crate) async fn new_attention_icon(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub(crate) async fn new_tool_tip(ctxt: &SignalContext<'_>) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub(crate) async fn new_status(ctxt: &SignalContext<'_>, status: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub(crate) async fn x_ayatana_new_label(
+        ctxt: &SignalContext<'_>,
+        label: &str,
+        guide: &str,
+    ) -> zbus::Result<()>;
+}
@@ -0,0 +1,624 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! The `com.canonical.dbusmenu` interface used to export the tray menu to the host.
+//!
+//! muda only hands us a [`gtk::Menu`], so the menu is walked on the gtk thread into
+//! a [`MenuItem`] tree the object server can read from any thread. Events coming
+//! from the host are dispatched back to the gtk thread where the matching gtk item
+//! is activated, which makes muda emit its usual [`MenuEvent`](crate::menu::MenuEvent).
+//!
+//! See <https://github.com/AyatanaIndicators/libdbusmenu/blob/master/libdbusmenu-glib/dbus-menu.xml>.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use gtk::{gdk, prelude::*};
+use zbus::{
+    fdo, interface,
+    zvariant::{OwnedValue, StructureBuilder, Value},
+    SignalContext,
+};
+
+pub(crate) const MENU_PATH: &str = "/MenuBar";
+
+thread_local! {
+    /// Menus of the tray icons created on this thread, keyed by their internal id.
+    ///
+    /// gtk widgets can't leave the thread they were created on, so the D-Bus
+    /// handlers look them up here after dispatching back to the gtk main context.
+    static MENUS: RefCell<HashMap<u32, ExportedMenu>> = RefCell::new(HashMap::new());
+}
+
+/// The kind of a [`MenuItem`], which decides how hosts draw it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum MenuItemKind {
+    #[default]
+    Standard,
+    Separator,
+    Check(bool),
+}
+
+/// A snapshot of a gtk menu item.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct MenuItem {
+    pub(crate) id: i32,
+    pub(crate) kind: MenuItemKind,
+    pub(crate) label: String,
+    pub(crate) enabled: bool,
+    pub(crate) visible: bool,
+    /// PNG encoded icon.
+    pub(crate) icon: Option<Vec<u8>>,
+    /// Key names of the accelerator, modifiers first, e.g. `["Control", "q"]`.
+    pub(crate) shortcut: Option<Vec<String>>,
+    pub(crate) children: Vec<MenuItem>,
+}
+
+impl MenuItem {
+    fn find(&self, id: i32) -> Option<&MenuItem> {
+        if self.id == id {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(id))
+    }
+
+    /// Properties of the item as described by the spec, only values that differ
+    /// from the spec defaults are included.
+    fn properties(&self, names: &[String]) -> HashMap<String, OwnedValue> {
+        let mut properties = HashMap::new();
+        let mut insert = |name: &str, value: Value<'_>| {
+            if names.is_empty() || names.iter().any(|n| n == name) {
+                properties.insert(name.to_string(), owned(value));
+            }
+        };
+
+        // the root item is never drawn, it only holds the menu
+        if self.id == 0 {
+            insert("children-display", Value::from("submenu"));
+            return properties;
+        }
+
+        match self.kind {
+            MenuItemKind::Standard => {}
+            MenuItemKind::Separator => insert("type", Value::from("separator")),
+            MenuItemKind::Check(checked) => {
+                insert("toggle-type", Value::from("checkmark"));
+                insert("toggle-state", Value::from(checked as i32));
+            }
+        }
+        if self.kind != MenuItemKind::Separator {
+            insert("label", Value::from(self.label.as_str()));
+        }
+        if !self.enabled {
+            insert("enabled", Value::from(false));
+        }
+        if !self.visible {
+            insert("visible", Value::from(false));
+        }
+        if let Some(icon) = &self.icon {
+            insert("icon-data", Value::from(icon.as_slice()));
+        }
+        if let Some(shortcut) = &self.shortcut {
+            insert("shortcut", Value::from(vec![shortcut.clone()]));
+        }
+        if !self.children.is_empty() {
+            insert("children-display", Value::from("submenu"));
+        }
+
+        properties
+    }
+
+    /// Builds the layout of this item, `depth` is how many levels of children to
+    /// include, `-1` includes all of them.
+    fn layout(&self, depth: i32, names: &[String]) -> Layout {
+        let children = if depth == 0 {
+            Vec::new()
+        } else {
+            self.children
+                .iter()
+                .map(|child| layout_to_value(child.layout(depth - 1, names)))
+                .collect()
+        };
+
+        (self.id, self.properties(names), children)
+    }
+}
+
+/// The `(ia{sv}av)` layout of an item, as `(id, properties, children)` where
+/// children are boxed layouts of the same type.
+pub(crate) type Layout = (i32, HashMap<String, OwnedValue>, Vec<OwnedValue>);
+
+fn layout_to_value((id, properties, children): Layout) -> OwnedValue {
+    owned(Value::from(
+        StructureBuilder::new()
+            .add_field(id)
+            .add_field(properties)
+            .add_field(children)
+            .build(),
+    ))
+}
+
+fn owned(value: Value<'_>) -> OwnedValue {
+    // only values holding file descriptors can fail to convert
+    value.try_into().unwrap()
+}
+
+/// The exported menu, shared between the gtk thread which updates it and the object server.
+#[derive(Debug, Default)]
+pub(crate) struct MenuState {
+    pub(crate) revision: u32,
+    pub(crate) root: MenuItem,
+}
+
+pub(crate) struct DBusMenu {
+    internal_id: u32,
+    state: Arc<Mutex<MenuState>>,
+}
+
+impl DBusMenu {
+    pub(crate) fn new(internal_id: u32, state: Arc<Mutex<MenuState>>) -> Self {
+        Self { internal_id, state }
+    }
+
+    fn state(&self) -> MutexGuard<'_, MenuState> {
+        self.state.lock().unwrap()
+    }
+
+    fn dispatch_event(&self, id: i32, event_id: &str) {
+        if event_id == "clicked" {
+            let internal_id = self.internal_id;
+            gtk::glib::MainContext::default().invoke(move || activate(internal_id, id));
+        }
+    }
+
+    /// Walks the gtk menu again on the gtk thread and returns whether it changed,
+    /// muda updates the gtk items in place when the menu is modified.
+    async fn refresh(&self) -> bool {
+        let internal_id = self.internal_id;
+        let (sender, receiver) = futures_channel::oneshot::channel();
+        gtk::glib::MainContext::default().invoke(move || {
+            let _ = sender.send(refresh(internal_id));
+        });
+        receiver.await.unwrap_or(false)
+    }
+}
+
+#[interface(name = "com.canonical.dbusmenu")]
+impl DBusMenu {
+    fn get_layout(
+        &self,
+        parent_id: i32,
+        recursion_depth: i32,
+        property_names: Vec<String>,
+    ) -> fdo::Result<(u32, Layout)> {
+        let state = self.state();
+        let parent = state
+            .root
+            .find(parent_id)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("no menu item with id {parent_id}")))?;
+        Ok((
+            state.revision,
+            parent.layout(recursion_depth, &property_names),
+        ))
+    }
+
+    fn get_group_properties(
+        &self,
+        ids: Vec<i32>,
+        property_names: Vec<String>,
+    ) -> Vec<(i32, HashMap<String, OwnedValue>)> {
+        let state = self.state();
+        ids.into_iter()
+            .filter_map(|id| state.root.find(id))
+            .map(|item| (item.id, item.properties(&property_names)))
+            .collect()
+    }
+
+    fn get_property(&self, id: i32, name: String) -> fdo::Result<OwnedValue> {
+        self.state()
+            .root
+            .find(id)
+            .and_then(|item| item.properties(std::slice::from_ref(&name)).remove(&name))
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("no property {name} on item {id}")))
+    }
+
+    fn event(&self, id: i32, event_id: &str, _data: OwnedValue, _timestamp: u32) {
+        self.dispatch_event(id, event_id);
+    }
+
+    /// Returns the ids that were not found.
+    fn event_group(&self, events: Vec<(i32, String, OwnedValue, u32)>) -> Vec<i32> {
+        let mut id_errors = Vec::new();
+        for (id, event_id, _, _) in events {
+            if self.state().root.find(id).is_some() {
+                self.dispatch_event(id, &event_id);
+            } else {
+                id_errors.push(id);
+            }
+        }
+        id_errors
+    }
+
+    /// Called by hosts before showing the menu, returns whether the layout changed
+    /// since it was last exported, which is also announced with `LayoutUpdated`.
+    async fn about_to_show(&self, _id: i32) -> bool {
+        self.refresh().await
+    }
+
+    /// Returns the ids needing an update and the ids that were not found.
+    async fn about_to_show_group(&self, ids: Vec<i32>) -> (Vec<i32>, Vec<i32>) {
+        let changed = self.refresh().await;
+        let state = self.state();
+        let (updates_needed, id_errors): (Vec<_>, Vec<_>) = ids
+            .into_iter()
+            .partition(|id| state.root.find(*id).is_some());
+        if changed {
+            (updates_needed, id_errors)
+        } else {
+            (Vec::new(), id_errors)
+        }
+    }
+
+    #[zbus(property)]
+    fn version(&self) -> u32 {
+        3
+    }
+
+    #[zbus(property)]
+    fn text_direction(&self) -> &str {
+        "ltr"
+    }
+
+    #[zbus(property)]
+    fn status(&self) -> &str {
+        "normal"
+    }
+
+    #[zbus(property)]
+    fn icon_theme_path(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(signal)]
+    pub(crate) async fn layout_updated(
+        ctxt: &SignalContext<'_>,
+        revision: u32,
+        parent: i32,
+    ) -> zbus::Result<()>;
+}
+
+/// A menu exported for a tray icon along with the gtk items matching its ids.
+struct ExportedMenu {
+    menu: Option<gtk::Menu>,
+    /// gtk item of each menu item, the item with id `n` is at `n - 1`.
+    items: Vec<gtk::MenuItem>,
+    state: Arc<Mutex<MenuState>>,
+    ctxt: SignalContext<'static>,
+}
+
+impl ExportedMenu {
+    /// Walks the menu again, the revision is only bumped when something changed.
+    fn refresh(&mut self) -> bool {
+        self.items.clear();
+        let mut root = MenuItem::default();
+        if let Some(menu) = &self.menu {
+            root.children = walk_menu(menu, &mut self.items);
+        }
+
+        let revision = {
+            let mut state = self.state.lock().unwrap();
+            if state.root == root {
+                return false;
+            }
+            state.root = root;
+            state.revision += 1;
+            state.revision
+        };
+
+        let _ = zbus::block_on(DBusMenu::layout_updated(&self.ctxt, revision, 0));
+        true
+    }
+}
+
+/// Starts exporting `menu` for the tray icon with the `id` internal id.
+///
+/// Must be called on the thread that created the tray icon.
+pub(crate) fn register(
+    id: u32,
+    menu: Option<gtk::Menu>,
+    state: Arc<Mutex<MenuState>>,
+    ctxt: SignalContext<'static>,
+) {
+    let mut exported = ExportedMenu {
+        menu,
+        items: Vec::new(),
+        state,
+        ctxt,
+    };
+    exported.refresh();
+    MENUS.with(|menus| menus.borrow_mut().insert(id, exported));
+}
+
+pub(crate) fn unregister(id: u32) {
+    MENUS.with(|menus| menus.borrow_mut().remove(&id));
+}
+
+/// Replaces the menu exported for the tray icon with the `id` internal id.
+pub(crate) fn set_menu(id: u32, menu: Option<gtk::Menu>) {
+    with_menu(id, |exported| {
+        exported.menu = menu;
+        exported.refresh();
+    });
+}
+
+/// Pops up the gtk menu, for hosts that ask the item to show its own menu.
+///
+/// Can be called from any thread, the menu is shown from the gtk main loop.
+pub(crate) fn show(id: u32) {
+    gtk::glib::MainContext::default().invoke(move || {
+        with_menu(id, |exported| {
+            if let Some(menu) = &exported.menu {
+                menu.popup_at_pointer(None);
+            }
+        });
+    });
+}
+
+fn refresh(id: u32) -> bool {
+    with_menu(id, ExportedMenu::refresh).unwrap_or(false)
+}
+
+fn activate(id: u32, item_id: i32) {
+    let item = MENUS.with(|menus| {
+        let menus = menus.borrow();
+        let items = &menus.get(&id)?.items;
+        usize::try_from(item_id - 1)
+            .ok()
+            .and_then(|i| items.get(i).cloned())
+    });
+
+    // activating might re-enter this module through muda's handlers, so no borrow is held
+    if let Some(item) = item {
+        if item.submenu().is_none() {
+            item.activate();
+            refresh(id);
+        }
+    }
+}
+
+fn with_menu<R>(id: u32, f: impl FnOnce(&mut ExportedMenu) -> R) -> Option<R> {
+    MENUS.with(|menus| menus.borrow_mut().get_mut(&id).map(f))
+}
+
+/// Walks the items of `menu`, pushing each gtk item to `items` so that its id is its position + 1.
+fn walk_menu(menu: &gtk::Menu, items: &mut Vec<gtk::MenuItem>) -> Vec<MenuItem> {
+    menu.children()
+        .into_iter()
+        .filter_map(|child| child.downcast::<gtk::MenuItem>().ok())
+        .map(|item| {
+            items.push(item.clone());
+            let mut snapshot = MenuItem {
+                id: items.len() as i32,
+                enabled: item.is_sensitive(),
+                visible: item.is_visible(),
+                ..Default::default()
+            };
+
+            if item.is::<gtk::SeparatorMenuItem>() {
+                snapshot.kind = MenuItemKind::Separator;
+                return snapshot;
+            }
+            if let Some(check) = item.downcast_ref::<gtk::CheckMenuItem>() {
+                snapshot.kind = MenuItemKind::Check(check.is_active());
+            }
+
+            // icon items hold their image and label in a box instead of a plain label
+            let mut widgets = vec![];
+            if let Some(child) = item.child() {
+                match child.downcast::<gtk::Box>() {
+                    Ok(container) => widgets.extend(container.children()),
+                    Err(child) => widgets.push(child),
+                }
+            }
+            for widget in widgets {
+                if let Some(label) = widget.downcast_ref::<gtk::AccelLabel>() {
+                    snapshot.label = label.label().to_string();
+                    let (key, mods) = label.accel();
+                    snapshot.shortcut = shortcut(key, mods);
+                } else if let Some(image) = widget.downcast_ref::<gtk::Image>() {
+                    snapshot.icon = image
+                        .pixbuf()
+                        .and_then(|pixbuf| pixbuf.save_to_bufferv("png", &[]).ok());
+                }
+            }
+
+            if let Some(submenu) = item.submenu().and_then(|s| s.downcast::<gtk::Menu>().ok()) {
+                snapshot.children = walk_menu(&submenu, items);
+            }
+
+            snapshot
+        })
+        .collect()
+}
+
+/// Converts a gtk accelerator to the key names dbusmenu expects.
+fn shortcut(key: u32, mods: gdk::ModifierType) -> Option<Vec<String>> {
+    if key == 0 {
+        return None;
+    }
+
+    let mut shortcut = Vec::new();
+    for (modifier, name) in [
+        (gdk::ModifierType::CONTROL_MASK, "Control"),
+        (gdk::ModifierType::MOD1_MASK, "Alt"),
+        (gdk::ModifierType::SHIFT_MASK, "Shift"),
+        (
+            gdk::ModifierType::SUPER_MASK | gdk::ModifierType::META_MASK,
+            "Super",
+        ),
+    ] {
+        if mods.intersects(modifier) {
+            shortcut.push(name.to_string());
+        }
+    }
+    shortcut.push(gdk::keys::Key::from(key).name()?.to_string());
+
+    Some(shortcut)
+}
+
+#[cfg(test)]
+mod tests {
+    //! These tests need a session bus and an X server, run them with
+    //! `xvfb-run dbus-run-session -- cargo test -- --include-ignored`.
+
+    use std::time::{Duration, Instant};
+
+    use zbus::{
+        blocking::{Connection, Proxy},
+        zvariant::OwnedValue,
+    };
+
+    use super::*;
+    use crate::menu::{
+        CheckMenuItem, ContextMenu, Icon, IconMenuItem, Menu, MenuEvent, MenuItem as MudaMenuItem,
+        PredefinedMenuItem, Submenu,
+    };
+
+    /// Runs the gtk main loop until `f` returns `Some`.
+    fn run_until<T>(mut f: impl FnMut() -> Option<T>) -> T {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(value) = f() {
+                return value;
+            }
+            assert!(Instant::now() < deadline, "timed out");
+            gtk::main_iteration_do(false);
+        }
+    }
+
+    /// Runs `f` on another thread, for calls answered from the gtk main loop.
+    fn off_thread<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+        std::thread::scope(|scope| {
+            let call = scope.spawn(f);
+            run_until(|| call.is_finished().then_some(()));
+            call.join().unwrap()
+        })
+    }
+
+    #[test]
+    #[ignore = "needs a session bus and an X server"]
+    fn exports_muda_menus_and_routes_clicks() {
+        gtk::init().unwrap();
+
+        let quit = MudaMenuItem::new("&Quit", true, None);
+        let check = CheckMenuItem::new("Check", false, true, None);
+        let icon = Icon::from_rgba(vec![255; 16 * 16 * 4], 16, 16).unwrap();
+        let icon_item = IconMenuItem::new("Icon", true, Some(icon), None);
+        let nested = MudaMenuItem::new("Nested", true, None);
+        let submenu = Submenu::with_items("Submenu", true, &[&nested]).unwrap();
+        let menu = Menu::with_items(&[
+            &quit,
+            &PredefinedMenuItem::separator(),
+            &check,
+            &icon_item,
+            &submenu,
+        ])
+        .unwrap();
+
+        // the gtk walk, ids follow the order of the items
+        let mut items = Vec::new();
+        let walked = walk_menu(&menu.gtk_context_menu(), &mut items);
+        assert_eq!(items.len(), 6);
+        let summary = walked
+            .iter()
+            .map(|item| {
+                (
+                    item.id,
+                    item.kind,
+                    item.label.as_str(),
+                    item.enabled,
+                    item.children.iter().map(|c| c.id).collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (1, MenuItemKind::Standard, "_Quit", true, vec![]),
+                (2, MenuItemKind::Separator, "", true, vec![]),
+                (3, MenuItemKind::Check(true), "Check", false, vec![]),
+                (4, MenuItemKind::Standard, "Icon", true, vec![]),
+                (5, MenuItemKind::Standard, "Submenu", true, vec![6]),
+            ]
+        );
+        assert!(walked.iter().all(|item| item.visible));
+        assert!(walked[3].icon.is_some());
+        assert_eq!(walked[4].children[0].label, "Nested");
+
+        // exporting it
+        let state = Arc::new(Mutex::new(MenuState::default()));
+        let conn = zbus::blocking::connection::Builder::session()
+            .unwrap()
+            .serve_at(MENU_PATH, DBusMenu::new(u32::MAX, state.clone()))
+            .unwrap()
+            .build()
+            .unwrap();
+        register(
+            u32::MAX,
+            Some(menu.gtk_context_menu()),
+            state.clone(),
+            SignalContext::new(conn.inner(), MENU_PATH).unwrap(),
+        );
+        assert_eq!(state.lock().unwrap().root.children, walked);
+        let revision = state.lock().unwrap().revision;
+
+        let client = Connection::session().unwrap();
+        let proxy: Proxy = zbus::blocking::proxy::Builder::new(&client)
+            .destination(conn.unique_name().unwrap().to_owned())
+            .unwrap()
+            .path(MENU_PATH)
+            .unwrap()
+            .interface("com.canonical.dbusmenu")
+            .unwrap()
+            .build()
+            .unwrap();
+        let property =
+            |id: i32, name: &str| -> OwnedValue { proxy.call("GetProperty", &(id, name)).unwrap() };
+
+        // opening the menu only updates it when muda changed it
+        let about_to_show =
+            || -> bool { off_thread(|| proxy.call("AboutToShow", &(0i32)).unwrap()) };
+        assert!(!about_to_show());
+        assert_eq!(state.lock().unwrap().revision, revision);
+        quit.set_text("E&xit");
+        assert!(about_to_show());
+        assert_eq!(state.lock().unwrap().revision, revision + 1);
+        assert_eq!(String::try_from(property(1, "label")).unwrap(), "E_xit");
+        let (updates_needed, id_errors): (Vec<i32>, Vec<i32>) =
+            off_thread(|| proxy.call("AboutToShowGroup", &(vec![1i32, 42])).unwrap());
+        assert!(updates_needed.is_empty());
+        assert_eq!(id_errors, [42]);
+
+        // clicks come back as muda events
+        while MenuEvent::receiver().try_recv().is_ok() {}
+        proxy
+            .call_method("Event", &(1i32, "clicked", OwnedValue::from(0i32), 0u32))
+            .unwrap();
+        let event = run_until(|| MenuEvent::receiver().try_recv().ok());
+        assert_eq!(event.id(), quit.id());
+
+        proxy
+            .call_method("Event", &(3i32, "clicked", OwnedValue::from(0i32), 0u32))
+            .unwrap();
+        let event = run_until(|| MenuEvent::receiver().try_recv().ok());
+        assert_eq!(event.id(), check.id());
+        assert!(!check.is_checked());
+        assert_eq!(i32::try_from(property(3, "toggle-state")).unwrap(), 0);
+
+        unregister(u32::MAX);
+    }
+}
@@ -0,0 +1,805 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+mod host;
+mod icon;
+mod item;
+mod menu;
+mod theme;
+mod watcher;
+mod xembed;
+
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use zbus::{blocking::Connection, SignalContext};
+
+use crate::icon::Icon;
+pub(crate) use host::host_status;
+pub(crate) use icon::PlatformIcon;
+
+use self::{
+    item::{ItemState, Status, StatusNotifierItem, ITEM_PATH},
+    menu::{DBusMenu, MenuState, MENU_PATH},
+    watcher::StatusNotifierWatcherProxyBlocking,
+    xembed::XEmbedFallback,
+};
+use crate::{TrayIconAttributes, TrayIconId, COUNTER};
+
+pub struct TrayIcon {
+    id: u32,
+    name: String,
+    conn: Connection,
+    ctxt: SignalContext<'static>,
+    state: Arc<Mutex<ItemState>>,
+    temp_dir_path: Option<PathBuf>,
+    path: Option<PathBuf>,
+    counter: u32,
+    menu: Option<Box<dyn muda::ContextMenu>>,
+    icon: Option<Icon>,
+    /// The frame of the running animation, shown instead of `icon`.
+    animation_frame: Option<Icon>,
+    attention_icon: Option<Icon>,
+    overlay_icon: Option<Icon>,
+    visible: bool,
+    attention: bool,
+    /// Fallback used on X11 panels without a StatusNotifierItem host.
+    xembed: Arc<Mutex<XEmbedFallback>>,
+}
+
+impl TrayIcon {
+    pub fn new(id: TrayIconId, attrs: TrayIconAttributes) -> crate::Result<Self> {
+        let internal_id = COUNTER.next();
+
+        let mut state = ItemState::default();
+        let path = update_icon(
+            &mut state,
+            attrs.icon.as_ref(),
+            attrs.temp_dir_path.as_ref(),
+            internal_id,
+            0,
+        )?;
+        if let Some(app_id) = attrs.app_id {
+            state.id = app_id;
+        }
+        state.category = attrs.category;
+        if let Some(title) = attrs.title {
+            state.title = title;
+        }
+        if let Some(tooltip) = attrs.tooltip {
+            state.tooltip = tooltip;
+        }
+        (state.attention_icon_name, state.attention_icon_pixmap) =
+            icon_properties(attrs.attention_icon.as_ref());
+        state.has_menu = attrs.menu.is_some();
+        let state = Arc::new(Mutex::new(state));
+        let menu_state = Arc::new(Mutex::new(MenuState::default()));
+
+        // item names must be unique on the bus, this follows the scheme recommended by the spec
+        let name = format!(
+            "org.kde.StatusNotifierItem-{}-{}",
+            std::process::id(),
+            internal_id
+        );
+        let conn = zbus::blocking::connection::Builder::session()?
+            .name(name.as_str())?
+            .serve_at(
+                ITEM_PATH,
+                StatusNotifierItem::new(id.clone(), internal_id, state.clone()),
+            )?
+            .serve_at(MENU_PATH, DBusMenu::new(internal_id, menu_state.clone()))?
+            .build()?;
+        let ctxt = SignalContext::new(conn.inner(), ITEM_PATH)?;
+
+        menu::register(
+            internal_id,
+            attrs.menu.as_ref().map(|m| m.gtk_context_menu()),
+            menu_state,
+            SignalContext::new(conn.inner(), MENU_PATH)?,
+        );
+
+        // The watcher might not be running yet, in which case the item stays
+        // exported on the bus and registers once it shows up.
+        let _ = StatusNotifierWatcherProxyBlocking::new(&conn)
+            .and_then(|watcher| watcher.register_status_notifier_item(&name));
+
+        // Panels like i3bar or stalonetray only implement the older XEmbed system tray.
+        let mut xembed =
+            XEmbedFallback::new(id.clone(), internal_id, state.lock().unwrap().title.clone());
+        xembed.set_icon(attrs.icon.as_ref().map(|i| &i.inner))?;
+        let xembed = Arc::new(Mutex::new(xembed));
+        host::add_item(internal_id, id, name.clone(), xembed.clone());
+
+        Ok(Self {
+            id: internal_id,
+            name,
+            conn,
+            ctxt,
+            state,
+            path,
+            temp_dir_path: attrs.temp_dir_path,
+            counter: 0,
+            menu: attrs.menu,
+            icon: attrs.icon,
+            animation_frame: None,
+            attention_icon: attrs.attention_icon,
+            overlay_icon: None,
+            visible: true,
+            attention: false,
+            xembed,
+        })
+    }
+
+    pub fn set_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        if let Some(path) = self.path.take() {
+            let _ = std::fs::remove_file(path);
+        }
+
+        self.counter += 1;
+
+        self.path = update_icon(
+            &mut self.state.lock().unwrap(),
+            with_overlay(icon.as_ref(), self.overlay_icon.as_ref()).as_deref(),
+            self.temp_dir_path.as_ref(),
+            self.id,
+            self.counter,
+        )?;
+
+        self.icon = icon;
+        self.animation_frame = None;
+        self.update_xembed_icon()?;
+
+        zbus::block_on(StatusNotifierItem::new_icon(&self.ctxt))?;
+        // the tooltip shows the icon too
+        if !self.state.lock().unwrap().tooltip.is_empty() {
+            zbus::block_on(StatusNotifierItem::new_tool_tip(&self.ctxt))?;
+        }
+
+        Ok(())
+    }
+
+    /// Frames are only sent as pixmaps, so animations don't write a file per frame.
+    pub fn set_animation_frame(&mut self, frame: Option<Icon>) -> crate::Result<()> {
+        let Some(frame) = frame else {
+            return self.set_icon(self.icon.clone());
+        };
+
+        update_icon(
+            &mut self.state.lock().unwrap(),
+            with_overlay(Some(&frame), self.overlay_icon.as_ref()).as_deref(),
+            None,
+            self.id,
+            self.counter,
+        )?;
+        self.animation_frame = Some(frame);
+        self.update_xembed_icon()?;
+
+        zbus::block_on(StatusNotifierItem::new_icon(&self.ctxt))?;
+
+        Ok(())
+    }
+
+    pub fn set_attention_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        {
+            let mut state = self.state.lock().unwrap();
+            (state.attention_icon_name, state.attention_icon_pixmap) =
+                icon_properties(with_overlay(icon.as_ref(), self.overlay_icon.as_ref()).as_deref());
+        }
+
+        self.attention_icon = icon;
+        self.update_xembed_icon()?;
+
+        zbus::block_on(StatusNotifierItem::new_attention_icon(&self.ctxt))?;
+
+        Ok(())
+    }
+
+    /// Few hosts besides KDE draw the `OverlayIconPixmap`, so the overlay is drawn onto
+    /// the icon and attention icon instead, like on the other platforms.
+    pub fn set_overlay_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.overlay_icon = icon;
+
+        match self.animation_frame.clone() {
+            Some(frame) => self.set_animation_frame(Some(frame))?,
+            None => self.set_icon(self.icon.clone())?,
+        }
+        self.set_attention_icon(self.attention_icon.clone())
+    }
+
+    pub fn set_attention(&mut self, attention: bool) -> crate::Result<()> {
+        self.attention = attention;
+        self.update_xembed_icon()?;
+        self.update_status()
+    }
+
+    /// XEmbed trays have no notion of attention or overlays, so the icon
+    /// itself is swapped and the overlay drawn onto it.
+    fn update_xembed_icon(&self) -> crate::Result<()> {
+        let icon = match &self.attention_icon {
+            Some(attention_icon) if self.attention => Some(attention_icon),
+            _ => self.animation_frame.as_ref().or(self.icon.as_ref()),
+        };
+        let icon = with_overlay(icon, self.overlay_icon.as_ref());
+        self.xembed
+            .lock()
+            .unwrap()
+            .set_icon(icon.as_deref().map(|i| &i.inner))?;
+
+        Ok(())
+    }
+
+    fn update_status(&self) -> crate::Result<()> {
+        let status = if !self.visible {
+            Status::Passive
+        } else if self.attention {
+            Status::NeedsAttention
+        } else {
+            Status::Active
+        };
+        self.state.lock().unwrap().status = status;
+
+        zbus::block_on(StatusNotifierItem::new_status(&self.ctxt, status.as_str()))?;
+
+        Ok(())
+    }
+
+    pub fn set_menu(&mut self, menu: Option<Box<dyn crate::menu::ContextMenu>>) {
+        menu::set_menu(self.id, menu.as_ref().map(|m| m.gtk_context_menu()));
+
+        let has_menu = menu.is_some();
+        let had_menu = std::mem::replace(&mut self.state.lock().unwrap().has_menu, has_menu);
+        if had_menu != has_menu {
+            let _ = self.notify_menu_changed();
+        }
+
+        self.menu = menu;
+    }
+
+    /// Lets hosts know the `Menu` property changed.
+    fn notify_menu_changed(&self) -> crate::Result<()> {
+        let iface = self
+            .conn
+            .object_server()
+            .interface::<_, StatusNotifierItem>(ITEM_PATH)?;
+        zbus::block_on(iface.get().menu_changed(iface.signal_context()))?;
+        Ok(())
+    }
+
+    pub fn set_tooltip<S: AsRef<str>>(&mut self, tooltip: Option<S>) -> crate::Result<()> {
+        self.state.lock().unwrap().tooltip = tooltip
+            .as_ref()
+            .map(|t| t.as_ref())
+            .unwrap_or("")
+            .to_string();
+
+        zbus::block_on(StatusNotifierItem::new_tool_tip(&self.ctxt))?;
+
+        Ok(())
+    }
+
+    pub fn set_title<S: AsRef<str>>(&mut self, title: Option<S>) {
+        let title = title.as_ref().map(|t| t.as_ref()).unwrap_or("").to_string();
+        self.state.lock().unwrap().title.clone_from(&title);
+
+        let _ = self.xembed.lock().unwrap().set_title(&title);
+
+        let _ = zbus::block_on(async {
+            StatusNotifierItem::new_title(&self.ctxt).await?;
+            StatusNotifierItem::x_ayatana_new_label(&self.ctxt, &title, "").await
+        });
+    }
+
+    pub fn set_visible(&mut self, visible: bool) -> crate::Result<()> {
+        self.visible = visible;
+
+        self.xembed.lock().unwrap().set_visible(visible)?;
+
+        self.update_status()
+    }
+
+    pub fn set_temp_dir_path<P: AsRef<Path>>(&mut self, path: Option<P>) {
+        self.temp_dir_path = path.map(|p| p.as_ref().to_path_buf());
+    }
+
+    pub fn rect(&self) -> Option<crate::Rect> {
+        self.xembed.lock().unwrap().rect()
+    }
+}
+
+impl Drop for TrayIcon {
+    fn drop(&mut self) {
+        // Releasing our bus name is enough for the watcher to unregister the item.
+        let _ = self.conn.release_name(self.name.as_str());
+        menu::unregister(self.id);
+        host::remove_item(self.id);
+        if let Some(path) = &self.path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Runs `f` on the GTK main loop after `delay`.
+pub(crate) fn run_after(delay: Duration, f: impl FnOnce() + 'static) {
+    gtk::glib::timeout_add_local_once(delay, f);
+}
+
+/// Sets the icon properties of `state` to `icon`.
+///
+/// The icon is always sent as in-memory pixmaps, it is only written to disk
+/// and exported by name when `temp_dir_path` is set, in which case the path
+/// of the written file is returned.
+fn update_icon(
+    state: &mut ItemState,
+    icon: Option<&Icon>,
+    temp_dir_path: Option<&PathBuf>,
+    id: u32,
+    counter: u32,
+) -> crate::Result<Option<PathBuf>> {
+    (state.icon_name, state.icon_pixmap) = icon_properties(icon);
+    state.icon_theme_path.clear();
+
+    // themed icons are looked up by the host, so they don't need a file
+    let (Some(icon), Some(temp_dir_path)) = (icon, temp_dir_path) else {
+        return Ok(None);
+    };
+    if icon.inner.theme_name.is_some() {
+        return Ok(None);
+    }
+
+    let (parent_path, icon_path) = temp_icon_path(temp_dir_path, id, counter)?;
+    icon.inner.write_to_png(&icon_path)?;
+    state.icon_theme_path = parent_path.to_string_lossy().into_owned();
+    state.icon_name = icon_path.to_string_lossy().into_owned();
+
+    Ok(Some(icon_path))
+}
+
+/// Returns the name and pixmaps exported for `icon`, the name is only set for themed icons.
+fn icon_properties(icon: Option<&Icon>) -> (String, Vec<item::Pixmap>) {
+    let Some(icon) = icon else {
+        return Default::default();
+    };
+    (
+        icon.inner.theme_name.clone().unwrap_or_default(),
+        icon.inner.to_pixmaps(),
+    )
+}
+
+/// Returns `icon` with `overlay` drawn over its bottom-right corner, or as is without
+/// an overlay or if it can't be drawn.
+fn with_overlay<'a>(icon: Option<&'a Icon>, overlay: Option<&Icon>) -> Option<Cow<'a, Icon>> {
+    let icon = icon?;
+    Some(
+        overlay
+            .and_then(|overlay| icon.with_overlay(overlay))
+            .map_or(Cow::Borrowed(icon), Cow::Owned),
+    )
+}
+
+/// Generates an icon path in `temp_icon_dir`, creating it if needed.
+fn temp_icon_path(
+    temp_icon_dir: &Path,
+    id: u32,
+    counter: u32,
+) -> std::io::Result<(PathBuf, PathBuf)> {
+    std::fs::create_dir_all(temp_icon_dir)?;
+    let icon_path = temp_icon_dir.join(format!("tray-icon-{}-{}.png", id, counter));
+    Ok((temp_icon_dir.to_path_buf(), icon_path))
+}
+
+#[test]
+fn temp_icon_path_uses_given_dir() {
+    let dir = std::env::temp_dir().join("tray-icon-path-test");
+
+    let (parent, file) = temp_icon_path(&dir, 1, 2).unwrap();
+
+    assert_eq!(parent, dir);
+    assert!(dir.is_dir());
+    assert_eq!(file, dir.join("tray-icon-1-2.png"));
+}
+
+#[cfg(test)]
+mod tests {
+    //! These tests need a session bus, run them with
+    //! `dbus-run-session -- cargo test -- --include-ignored`.
+
+    use std::{
+        collections::{HashMap, VecDeque},
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    use once_cell::sync::Lazy;
+    use zbus::{
+        blocking::{Connection, Proxy},
+        zvariant::{OwnedObjectPath, OwnedValue, Value},
+    };
+
+    use super::{
+        item::{Pixmap, ToolTip},
+        menu::{DBusMenu, Layout, MenuItem, MenuItemKind, MenuState, MENU_PATH},
+        TrayIcon, ITEM_PATH,
+    };
+    use crate::{
+        dpi::PhysicalPosition, Category, HostStatus, Icon, MouseButton, MouseButtonState,
+        ScrollOrientation, TrayIconAttributes, TrayIconEvent, TrayIconId,
+    };
+
+    /// Stands in for the watcher normally provided by the panel.
+    struct FakeWatcher {
+        items: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[zbus::interface(name = "org.kde.StatusNotifierWatcher")]
+    impl FakeWatcher {
+        fn register_status_notifier_item(&self, service: &str) {
+            self.items.lock().unwrap().push(service.to_string());
+        }
+
+        #[zbus(property)]
+        fn is_status_notifier_host_registered(&self) -> bool {
+            true
+        }
+    }
+
+    struct Host {
+        conn: Connection,
+        items: Arc<Mutex<Vec<String>>>,
+    }
+
+    static HOST: Lazy<Host> = Lazy::new(|| {
+        let items = Arc::new(Mutex::new(Vec::new()));
+        let conn = zbus::blocking::connection::Builder::session()
+            .and_then(|builder| builder.name("org.kde.StatusNotifierWatcher"))
+            .and_then(|builder| {
+                builder.serve_at(
+                    "/StatusNotifierWatcher",
+                    FakeWatcher {
+                        items: items.clone(),
+                    },
+                )
+            })
+            .and_then(|builder| builder.build())
+            .expect("these tests need a session bus, run them with `dbus-run-session`");
+
+        Host { conn, items }
+    });
+
+    fn item_proxy<'a>(host: &'a Host, tray: &TrayIcon) -> Proxy<'a> {
+        zbus::blocking::proxy::Builder::new(&host.conn)
+            .destination(tray.name.clone())
+            .unwrap()
+            .path(ITEM_PATH)
+            .unwrap()
+            .interface("org.kde.StatusNotifierItem")
+            .unwrap()
+            .cache_properties(zbus::CacheProperties::No)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs a session bus"]
+    fn registers_and_exports_item() {
+        let host = &*HOST;
+
+        let mut tray = TrayIcon::new(
+            TrayIconId::new("test"),
+            TrayIconAttributes {
+                icon: Some(Icon::from_rgba(vec![255; 16], 2, 2).unwrap()),
+                title: Some("title".to_string()),
+                tooltip: Some("tooltip".to_string()),
+                app_id: Some("tray-icon-tests".to_string()),
+                category: Category::Hardware,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(host.items.lock().unwrap().contains(&tray.name));
+
+        let proxy = item_proxy(host, &tray);
+        assert_eq!(proxy.get_property::<String>("Title").unwrap(), "title");
+        assert_eq!(
+            proxy.get_property::<String>("Id").unwrap(),
+            "tray-icon-tests"
+        );
+        assert_eq!(
+            proxy.get_property::<String>("Category").unwrap(),
+            "Hardware"
+        );
+        assert_eq!(proxy.get_property::<String>("Status").unwrap(), "Active");
+        assert_eq!(proxy.get_property::<String>("IconName").unwrap(), "");
+        let pixmaps = proxy.get_property::<Vec<Pixmap>>("IconPixmap").unwrap();
+        assert_eq!(pixmaps, [(2, 2, vec![255; 16])]);
+
+        let menu_path = proxy.get_property::<OwnedObjectPath>("Menu").unwrap();
+        assert_eq!(menu_path.as_str(), "/NO_DBUSMENU");
+        let menu_proxy: Proxy = zbus::blocking::proxy::Builder::new(&host.conn)
+            .destination(tray.name.clone())
+            .unwrap()
+            .path(MENU_PATH)
+            .unwrap()
+            .interface("com.canonical.dbusmenu")
+            .unwrap()
+            .build()
+            .unwrap();
+        let get_layout = || -> (u32, Layout) {
+            menu_proxy
+                .call("GetLayout", &(0i32, -1i32, Vec::<String>::new()))
+                .unwrap()
+        };
+        // the layout didn't change, so neither did the revision
+        let (revision, _) = get_layout();
+        tray.set_menu(None);
+        let (new_revision, (_, _, children)) = get_layout();
+        assert_eq!(new_revision, revision);
+        assert!(children.is_empty());
+
+        tray.set_attention_icon(Some(Icon::from_rgba(vec![0; 16], 2, 2).unwrap()))
+            .unwrap();
+        tray.set_attention(true).unwrap();
+        assert_eq!(
+            proxy.get_property::<String>("Status").unwrap(),
+            "NeedsAttention"
+        );
+        let pixmaps = proxy
+            .get_property::<Vec<Pixmap>>("AttentionIconPixmap")
+            .unwrap();
+        assert_eq!(pixmaps, [(2, 2, vec![0; 16])]);
+
+        // the overlay is drawn onto the bottom-right pixel of both icons
+        tray.set_overlay_icon(Some(Icon::from_rgba(vec![128; 4], 1, 1).unwrap()))
+            .unwrap();
+        let pixmaps = proxy.get_property::<Vec<Pixmap>>("IconPixmap").unwrap();
+        assert_eq!(
+            pixmaps,
+            [(2, 2, [vec![255; 12], vec![255, 191, 191, 191]].concat())]
+        );
+        let pixmaps = proxy
+            .get_property::<Vec<Pixmap>>("AttentionIconPixmap")
+            .unwrap();
+        assert_eq!(pixmaps, [(2, 2, [vec![0; 12], vec![128; 4]].concat())]);
+        assert!(proxy
+            .get_property::<Vec<Pixmap>>("OverlayIconPixmap")
+            .unwrap()
+            .is_empty());
+        tray.set_overlay_icon(None).unwrap();
+        let pixmaps = proxy.get_property::<Vec<Pixmap>>("IconPixmap").unwrap();
+        assert_eq!(pixmaps, [(2, 2, vec![255; 16])]);
+
+        tray.set_title(Some("new title"));
+        tray.set_visible(false).unwrap();
+        assert_eq!(proxy.get_property::<String>("Title").unwrap(), "new title");
+        assert_eq!(proxy.get_property::<String>("Status").unwrap(), "Passive");
+
+        let tooltip = proxy.get_property::<ToolTip>("ToolTip").unwrap();
+        assert_eq!(
+            tooltip,
+            (
+                String::new(),
+                vec![(2, 2, vec![255; 16])],
+                "tooltip".to_string(),
+                String::new()
+            )
+        );
+        tray.set_tooltip(Some("new tooltip\nfirst line\nsecond line"))
+            .unwrap();
+        let (_, _, title, description) = proxy.get_property::<ToolTip>("ToolTip").unwrap();
+        assert_eq!(title, "new tooltip");
+        assert_eq!(description, "first line\nsecond line");
+
+        let temp_dir = std::env::temp_dir().join("tray-icon-tests");
+        tray.set_temp_dir_path(Some(&temp_dir));
+        tray.set_icon(Some(Icon::from_rgba(vec![255; 16], 2, 2).unwrap()))
+            .unwrap();
+        let icon_name = proxy.get_property::<String>("IconName").unwrap();
+        assert!(std::path::Path::new(&icon_name).starts_with(&temp_dir));
+        assert!(std::path::Path::new(&icon_name).exists());
+
+        tray.set_icon(None).unwrap();
+        assert_eq!(proxy.get_property::<String>("IconName").unwrap(), "");
+        assert!(proxy
+            .get_property::<Vec<Pixmap>>("IconPixmap")
+            .unwrap()
+            .is_empty());
+        assert!(!std::path::Path::new(&icon_name).exists());
+    }
+
+    /// Events received by any test, by tray icon id.
+    ///
+    /// Tests run in parallel and share the global event channel, so events for
+    /// other ids are kept here for the test waiting on them.
+    static EVENTS: Lazy<Mutex<HashMap<TrayIconId, VecDeque<TrayIconEvent>>>> =
+        Lazy::new(Default::default);
+
+    /// Collects the events emitted for `id` until `count` of them are received.
+    pub(super) fn recv_events(id: &TrayIconId, count: usize) -> Vec<TrayIconEvent> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut events = Vec::new();
+        while events.len() < count {
+            let received = EVENTS
+                .lock()
+                .unwrap()
+                .get_mut(id)
+                .and_then(|events| events.pop_front());
+            if let Some(event) = received {
+                events.push(event);
+                continue;
+            }
+
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match TrayIconEvent::receiver().recv_timeout(timeout.min(Duration::from_millis(50))) {
+                Ok(event) => EVENTS
+                    .lock()
+                    .unwrap()
+                    .entry(event.id().clone())
+                    .or_default()
+                    .push_back(event),
+                Err(_) if timeout.is_zero() => {
+                    panic!("received {} of {count} events for {id:?}", events.len())
+                }
+                Err(_) => {}
+            }
+        }
+        events
+    }
+
+    #[test]
+    #[ignore = "needs a session bus"]
+    fn reports_host_status() {
+        let _host = &*HOST;
+
+        assert_eq!(crate::host_status(), HostStatus::Available);
+    }
+
+    #[test]
+    #[ignore = "needs a session bus"]
+    fn emits_click_events() {
+        let host = &*HOST;
+
+        let id = TrayIconId::new("click-test");
+        let tray = TrayIcon::new(id.clone(), TrayIconAttributes::default()).unwrap();
+        let proxy = item_proxy(host, &tray);
+
+        for (method, expected) in [
+            ("Activate", MouseButton::Left),
+            ("SecondaryActivate", MouseButton::Middle),
+        ] {
+            proxy.call_method(method, &(10i32, 20i32)).unwrap();
+
+            let events = recv_events(&id, 2);
+            for (event, expected_state) in events
+                .iter()
+                .zip([MouseButtonState::Down, MouseButtonState::Up])
+            {
+                let TrayIconEvent::Click {
+                    position,
+                    button,
+                    button_state,
+                    ..
+                } = event
+                else {
+                    panic!("unexpected event {:?}", event);
+                };
+                assert_eq!(*button, expected);
+                assert_eq!(*button_state, expected_state);
+                assert_eq!(*position, PhysicalPosition::new(10., 20.));
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a session bus"]
+    fn emits_scroll_events() {
+        let host = &*HOST;
+
+        let id = TrayIconId::new("scroll-test");
+        let tray = TrayIcon::new(id.clone(), TrayIconAttributes::default()).unwrap();
+        let proxy = item_proxy(host, &tray);
+
+        // hosts send positive deltas when scrolling up or left
+        proxy.call_method("Scroll", &(-3i32, "horizontal")).unwrap();
+        proxy.call_method("Scroll", &(120i32, "vertical")).unwrap();
+
+        let events = recv_events(&id, 2);
+        let scrolls = events
+            .iter()
+            .map(|event| match event {
+                TrayIconEvent::Scroll {
+                    delta, orientation, ..
+                } => (*delta, *orientation),
+                _ => panic!("unexpected event {:?}", event),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            scrolls,
+            [
+                (3, ScrollOrientation::Horizontal),
+                (-120, ScrollOrientation::Vertical)
+            ]
+        );
+    }
+
+    #[test]
+    #[ignore = "needs a session bus"]
+    fn exports_menu_layout() {
+        let host = &*HOST;
+
+        let root = MenuItem {
+            children: vec![
+                MenuItem {
+                    id: 1,
+                    label: "_Quit".to_string(),
+                    enabled: true,
+                    visible: true,
+                    shortcut: Some(vec!["Control".to_string(), "q".to_string()]),
+                    ..Default::default()
+                },
+                MenuItem {
+                    id: 2,
+                    kind: MenuItemKind::Separator,
+                    enabled: true,
+                    visible: true,
+                    ..Default::default()
+                },
+                MenuItem {
+                    id: 3,
+                    kind: MenuItemKind::Check(true),
+                    label: "Check".to_string(),
+                    enabled: false,
+                    visible: true,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let state = Arc::new(Mutex::new(MenuState { revision: 4, root }));
+        let conn = zbus::blocking::connection::Builder::session()
+            .unwrap()
+            .serve_at(MENU_PATH, DBusMenu::new(0, state))
+            .unwrap()
+            .build()
+            .unwrap();
+        let proxy: Proxy = zbus::blocking::proxy::Builder::new(&host.conn)
+            .destination(conn.unique_name().unwrap().to_owned())
+            .unwrap()
+            .path(MENU_PATH)
+            .unwrap()
+            .interface("com.canonical.dbusmenu")
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let (revision, (id, _, children)): (u32, Layout) = proxy
+            .call("GetLayout", &(0i32, -1i32, Vec::<String>::new()))
+            .unwrap();
+        assert_eq!((revision, id, children.len()), (4, 0, 3));
+
+        let properties: Vec<(i32, HashMap<String, OwnedValue>)> = proxy
+            .call(
+                "GetGroupProperties",
+                &(vec![1i32, 2, 3], Vec::<String>::new()),
+            )
+            .unwrap();
+        let property = |id: usize, name: &str| {
+            properties[id - 1]
+                .1
+                .get(name)
+                .map(|v| Value::from(v.try_clone().unwrap()))
+        };
+        assert_eq!(property(1, "label"), Some(Value::from("_Quit")));
+        assert_eq!(
+            property(1, "shortcut"),
+            Some(Value::from(vec![vec!["Control", "q"]]))
+        );
+        assert_eq!(property(2, "type"), Some(Value::from("separator")));
+        assert_eq!(property(2, "label"), None);
+        assert_eq!(property(3, "toggle-state"), Some(Value::from(1i32)));
+        assert_eq!(property(3, "enabled"), Some(Value::from(false)));
+    }
+}
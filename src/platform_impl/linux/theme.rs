@@ -0,0 +1,440 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Icon lookup by name in freedesktop icon themes.
+//!
+//! See <https://specifications.freedesktop.org/icon-theme-spec/latest/>.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use gtk::prelude::GtkSettingsExt;
+
+/// The theme every other theme implicitly falls back to.
+const FALLBACK_THEME: &str = "hicolor";
+
+/// Extensions of the icon files we know how to load, in order of preference.
+///
+/// SVG icons are only rendered with the `svg` feature, hosts load them by name otherwise.
+const EXTENSIONS: &[&str] = &["png", "svg"];
+
+/// Returns the icon theme selected by the user.
+///
+/// This is read from the GTK settings, which are only available on the GTK thread
+/// once GTK is initialized, otherwise only the fallback theme is used.
+pub(crate) fn current_theme() -> String {
+    gtk::is_initialized_main_thread()
+        .then(gtk::Settings::default)
+        .flatten()
+        .and_then(|settings| settings.gtk_icon_theme_name())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| FALLBACK_THEME.to_string())
+}
+
+/// How icons in a theme directory can be scaled, see [`ThemeDir::matches_size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirType {
+    Fixed,
+    Scalable,
+    Threshold,
+}
+
+/// A subdirectory of a theme, described by its section in `index.theme`.
+#[derive(Debug)]
+struct ThemeDir {
+    path: String,
+    size: u32,
+    scale: u32,
+    min_size: u32,
+    max_size: u32,
+    threshold: u32,
+    dir_type: DirType,
+}
+
+impl ThemeDir {
+    fn from_section(path: &str, section: &HashMap<String, String>) -> Option<Self> {
+        let get = |key: &str| section.get(key).and_then(|v| v.parse::<u32>().ok());
+
+        let size = get("Size")?;
+        let dir_type = match section.get("Type").map(String::as_str) {
+            Some("Fixed") => DirType::Fixed,
+            Some("Scalable") => DirType::Scalable,
+            _ => DirType::Threshold,
+        };
+
+        Some(Self {
+            path: path.to_string(),
+            size,
+            scale: get("Scale").unwrap_or(1),
+            min_size: get("MinSize").unwrap_or(size),
+            max_size: get("MaxSize").unwrap_or(size),
+            threshold: get("Threshold").unwrap_or(2),
+            dir_type,
+        })
+    }
+
+    fn matches_size(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+
+        match self.dir_type {
+            DirType::Fixed => self.size == size,
+            DirType::Scalable => (self.min_size..=self.max_size).contains(&size),
+            DirType::Threshold => (self.size.saturating_sub(self.threshold)
+                ..=self.size + self.threshold)
+                .contains(&size),
+        }
+    }
+
+    fn size_distance(&self, size: u32, scale: u32) -> u32 {
+        let wanted = size * scale;
+        let (min, max) = match self.dir_type {
+            DirType::Fixed => (self.size, self.size),
+            DirType::Scalable => (self.min_size, self.max_size),
+            DirType::Threshold => (
+                self.size.saturating_sub(self.threshold),
+                self.size + self.threshold,
+            ),
+        };
+        let (min, max) = (min * self.scale, max * self.scale);
+
+        if wanted < min {
+            min - wanted
+        } else {
+            wanted.saturating_sub(max)
+        }
+    }
+}
+
+/// The parts of a theme's `index.theme` needed for lookups.
+#[derive(Debug)]
+struct Theme {
+    dirs: Vec<ThemeDir>,
+    inherits: Vec<String>,
+}
+
+impl Theme {
+    fn parse(index: &str) -> Self {
+        let sections = parse_ini(index);
+        let empty = HashMap::new();
+        let header = sections.get("Icon Theme").unwrap_or(&empty);
+        let list = |key: &str| -> Vec<String> {
+            header
+                .get(key)
+                .map(|v| {
+                    v.split(',')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        let mut dir_names = list("Directories");
+        dir_names.extend(list("ScaledDirectories"));
+
+        Self {
+            dirs: dir_names
+                .iter()
+                .filter_map(|dir| ThemeDir::from_section(dir, sections.get(dir)?))
+                .collect(),
+            inherits: list("Inherits"),
+        }
+    }
+}
+
+/// Parses the desktop entry style `index.theme` files into `section -> key -> value`.
+fn parse_ini(contents: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut sections = HashMap::new();
+    let mut current = None;
+
+    for line in contents.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = Some(
+                sections
+                    .entry(name.to_string())
+                    .or_insert_with(HashMap::new),
+            );
+        } else if let (Some(section), Some((key, value))) = (&mut current, line.split_once('=')) {
+            section.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+
+    sections
+}
+
+/// Looks up icons in the themes installed in a list of base directories.
+pub(crate) struct IconThemes {
+    base_dirs: Vec<PathBuf>,
+    themes: HashMap<String, Option<Theme>>,
+}
+
+impl IconThemes {
+    pub(crate) fn new(base_dirs: Vec<PathBuf>) -> Self {
+        Self {
+            base_dirs,
+            themes: HashMap::new(),
+        }
+    }
+
+    /// Uses the base directories defined by the spec, in order of precedence.
+    pub(crate) fn system() -> Self {
+        let mut base_dirs = Vec::new();
+        if let Some(home) = dirs::home_dir() {
+            base_dirs.push(home.join(".icons"));
+        }
+        if let Some(data_dir) = dirs::data_dir() {
+            base_dirs.push(data_dir.join("icons"));
+        }
+        let data_dirs = std::env::var("XDG_DATA_DIRS")
+            .ok()
+            .filter(|dirs| !dirs.is_empty())
+            .unwrap_or_else(|| "/usr/local/share/:/usr/share/".to_string());
+        base_dirs.extend(
+            std::env::split_paths(&data_dirs)
+                .filter(|dir| dir.is_absolute())
+                .map(|dir| dir.join("icons")),
+        );
+        base_dirs.push(PathBuf::from("/usr/share/pixmaps"));
+
+        Self::new(base_dirs)
+    }
+
+    /// Finds the file of the icon `name` closest to `size` at `scale` in `theme`,
+    /// the themes it inherits from, and then `hicolor`.
+    pub(crate) fn find_icon(
+        &mut self,
+        theme: &str,
+        name: &str,
+        size: u32,
+        scale: u32,
+    ) -> Option<PathBuf> {
+        let mut visited = Vec::new();
+        self.find_icon_helper(theme, name, size, scale, &mut visited)
+            .or_else(|| self.find_icon_helper(FALLBACK_THEME, name, size, scale, &mut visited))
+            .or_else(|| self.lookup_fallback_icon(name))
+    }
+
+    fn find_icon_helper(
+        &mut self,
+        theme: &str,
+        name: &str,
+        size: u32,
+        scale: u32,
+        visited: &mut Vec<String>,
+    ) -> Option<PathBuf> {
+        // themes can inherit from each other in a cycle or more than once
+        if visited.iter().any(|t| t == theme) {
+            return None;
+        }
+        visited.push(theme.to_string());
+
+        if let Some(path) = self.lookup_icon(theme, name, size, scale) {
+            return Some(path);
+        }
+
+        let parents = self
+            .theme(theme)
+            .map(|t| t.inherits.clone())
+            .unwrap_or_default();
+        parents
+            .iter()
+            .find_map(|parent| self.find_icon_helper(parent, name, size, scale, visited))
+    }
+
+    fn lookup_icon(
+        &mut self,
+        theme_name: &str,
+        name: &str,
+        size: u32,
+        scale: u32,
+    ) -> Option<PathBuf> {
+        let base_dirs = self.base_dirs.clone();
+        let theme = self.theme(theme_name)?;
+
+        let candidates = || {
+            theme.dirs.iter().flat_map(|dir| {
+                base_dirs.iter().flat_map(move |base| {
+                    EXTENSIONS.iter().map(move |ext| {
+                        let path = base
+                            .join(theme_name)
+                            .join(&dir.path)
+                            .join(format!("{name}.{ext}"));
+                        (dir, path)
+                    })
+                })
+            })
+        };
+
+        if let Some((_, path)) =
+            candidates().find(|(dir, path)| dir.matches_size(size, scale) && path.is_file())
+        {
+            return Some(path);
+        }
+
+        candidates()
+            .filter(|(_, path)| path.is_file())
+            .min_by_key(|(dir, _)| dir.size_distance(size, scale))
+            .map(|(_, path)| path)
+    }
+
+    /// Icons that aren't part of any theme are put directly in the base directories.
+    fn lookup_fallback_icon(&self, name: &str) -> Option<PathBuf> {
+        self.base_dirs.iter().find_map(|base| {
+            EXTENSIONS
+                .iter()
+                .map(|ext| base.join(format!("{name}.{ext}")))
+                .find(|path| path.is_file())
+        })
+    }
+
+    /// Reads the index of `name` from the first base directory containing it.
+    fn theme(&mut self, name: &str) -> Option<&Theme> {
+        let base_dirs = &self.base_dirs;
+        self.themes
+            .entry(name.to_string())
+            .or_insert_with(|| {
+                base_dirs.iter().find_map(|base| {
+                    let index = fs::read_to_string(base.join(name).join("index.theme")).ok()?;
+                    Some(Theme::parse(&index))
+                })
+            })
+            .as_ref()
+    }
+}
+
+/// Checks that `name` looks like an icon name rather than a path, so lookups stay
+/// inside the icon directories.
+pub(crate) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('/') && Path::new(name).file_name().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_icon(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+    }
+
+    /// Builds two base dirs with a `Test` theme inheriting from `Parent`, and `hicolor`.
+    fn fixture() -> (PathBuf, IconThemes) {
+        let root = std::env::temp_dir().join("tray-icon-theme-test");
+        let _ = fs::remove_dir_all(&root);
+        let (user, system) = (root.join("user"), root.join("system"));
+
+        fs::create_dir_all(user.join("Test")).unwrap();
+        fs::write(
+            user.join("Test/index.theme"),
+            "[Icon Theme]\n\
+             Name=Test\n\
+             Inherits=Parent,Test\n\
+             Directories=16x16/apps,48x48/apps,scalable/apps\n\
+             ScaledDirectories=16x16@2/apps\n\
+             \n\
+             [16x16/apps]\n\
+             Size=16\n\
+             Type=Fixed\n\
+             \n\
+             [16x16@2/apps]\n\
+             Size=16\n\
+             Scale=2\n\
+             Type=Fixed\n\
+             \n\
+             [48x48/apps]\n\
+             Size=48\n\
+             Type=Fixed\n\
+             \n\
+             [scalable/apps]\n\
+             Size=64\n\
+             MinSize=8\n\
+             MaxSize=512\n\
+             Type=Scalable\n",
+        )
+        .unwrap();
+        write_icon(&user.join("Test/16x16/apps/app.png"));
+        write_icon(&user.join("Test/16x16@2/apps/app.png"));
+        write_icon(&system.join("Test/48x48/apps/app.png"));
+        write_icon(&user.join("Test/scalable/apps/vector.svg"));
+
+        fs::create_dir_all(system.join("Parent")).unwrap();
+        fs::write(
+            system.join("Parent/index.theme"),
+            "[Icon Theme]\nDirectories=22x22\n\n[22x22]\nSize=22\n",
+        )
+        .unwrap();
+        write_icon(&system.join("Parent/22x22/inherited.png"));
+
+        fs::create_dir_all(system.join("hicolor")).unwrap();
+        fs::write(
+            system.join("hicolor/index.theme"),
+            "[Icon Theme]\nDirectories=32x32/apps\n\n[32x32/apps]\nSize=32\nType=Fixed\n",
+        )
+        .unwrap();
+        write_icon(&system.join("hicolor/32x32/apps/hicolor-only.png"));
+        write_icon(&system.join("loose.png"));
+
+        (root, IconThemes::new(vec![user, system]))
+    }
+
+    #[test]
+    fn finds_icons_following_the_spec() {
+        let (root, mut themes) = fixture();
+        let (user, system) = (root.join("user"), root.join("system"));
+
+        // exact matches, split across base dirs
+        assert_eq!(
+            themes.find_icon("Test", "app", 16, 1),
+            Some(user.join("Test/16x16/apps/app.png"))
+        );
+        assert_eq!(
+            themes.find_icon("Test", "app", 16, 2),
+            Some(user.join("Test/16x16@2/apps/app.png"))
+        );
+        assert_eq!(
+            themes.find_icon("Test", "app", 48, 1),
+            Some(system.join("Test/48x48/apps/app.png"))
+        );
+        // closest size
+        assert_eq!(
+            themes.find_icon("Test", "app", 40, 1),
+            Some(system.join("Test/48x48/apps/app.png"))
+        );
+        assert_eq!(
+            themes.find_icon("Test", "app", 24, 1),
+            Some(user.join("Test/16x16/apps/app.png"))
+        );
+        // inherited theme, the threshold type is the default
+        assert_eq!(
+            themes.find_icon("Test", "inherited", 24, 1),
+            Some(system.join("Parent/22x22/inherited.png"))
+        );
+        // hicolor, then the base dirs themselves
+        assert_eq!(
+            themes.find_icon("Test", "hicolor-only", 16, 1),
+            Some(system.join("hicolor/32x32/apps/hicolor-only.png"))
+        );
+        assert_eq!(
+            themes.find_icon("Missing", "loose", 16, 1),
+            Some(system.join("loose.png"))
+        );
+        // vector icons, like the symbolic icons of most themes
+        assert_eq!(
+            themes.find_icon("Test", "vector", 16, 1),
+            Some(user.join("Test/scalable/apps/vector.svg"))
+        );
+        // unknown names
+        assert_eq!(themes.find_icon("Test", "unknown", 16, 1), None);
+    }
+}
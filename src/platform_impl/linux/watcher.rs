@@ -0,0 +1,32 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! Client side of the `org.kde.StatusNotifierWatcher` interface.
+//!
+//! The watcher is owned by the panel (or a standalone daemon) and keeps track of
+//! every registered item, see <https://freedesktop.org/wiki/Specifications/StatusNotifierItem/StatusNotifierWatcher/>.
+
+use zbus::proxy;
+
+pub(crate) const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+
+#[proxy(
+    interface = "org.kde.StatusNotifierWatcher",
+    default_service = "org.kde.StatusNotifierWatcher",
+    default_path = "/StatusNotifierWatcher"
+)]
+pub(crate) trait StatusNotifierWatcher {
+    /// Registers an item, `service` is either a bus name or an object path on the caller's connection.
+    fn register_status_notifier_item(&self, service: &str) -> zbus::Result<()>;
+
+    /// Whether at least one host (a panel able to show items) is registered.
+    #[zbus(property)]
+    fn is_status_notifier_host_registered(&self) -> zbus::Result<bool>;
+
+    #[zbus(signal)]
+    fn status_notifier_host_registered(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn status_notifier_host_unregistered(&self) -> zbus::Result<()>;
+}
@@ -0,0 +1,1076 @@
+// Copyright 2022-2022 Tauri Programme within The Commons Conservancy
+// SPDX-License-Identifier: Apache-2.0
+// SPDX-License-Identifier: MIT
+
+//! A fallback for X11 panels that only implement the XEmbed based system tray,
+//! used when no StatusNotifierItem host is around.
+//!
+//! A small window is docked in the tray owning the `_NET_SYSTEM_TRAY_S{n}` selection
+//! and the icon is painted into it, see <https://specifications.freedesktop.org/systemtray-spec/systemtray-spec-latest.html>.
+
+use std::{
+    borrow::Cow,
+    sync::{Arc, Mutex},
+};
+
+use x11rb::{
+    connection::Connection,
+    errors::ReplyOrIdError,
+    protocol::{
+        xproto::{
+            Atom, AtomEnum, BackPixmap, ButtonPressEvent, ChangeWindowAttributesAux,
+            ClientMessageEvent, ColormapAlloc, ConnectionExt as _, CreateGCAux, CreateWindowAux,
+            EventMask, Gcontext, ImageFormat, ImageOrder, PropMode, Window, WindowClass,
+        },
+        Event,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+};
+
+use super::{host::Hosts, icon::PlatformIcon};
+use crate::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    MouseButton, MouseButtonState, Rect, ScrollOrientation, TrayIconEvent, TrayIconId,
+};
+
+/// Size of the window until the tray resizes it.
+const DEFAULT_SIZE: u16 = 24;
+
+const SYSTEM_TRAY_REQUEST_DOCK: u32 = 0;
+const XEMBED_MAPPED: u32 = 1;
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        MANAGER,
+        UTF8_STRING,
+        _NET_WM_NAME,
+        _NET_SYSTEM_TRAY_OPCODE,
+        _NET_SYSTEM_TRAY_VISUAL,
+        _XEMBED_INFO,
+    }
+}
+
+/// Follows the system tray of the X11 display, calling `on_change` from another
+/// thread whenever one starts or stops.
+///
+/// Returns whether a tray is running now.
+pub(crate) fn watch_tray(on_change: impl Fn(bool) + Send + 'static) -> bool {
+    let Ok((conn, screen_num)) = x11rb::connect(None) else {
+        return false;
+    };
+    let setup = || -> Result<_, ReplyOrIdError> {
+        let atoms = Atoms::new(&conn)?.reply()?;
+        let selection = tray_selection(&conn, screen_num)?;
+        // new trays announce themselves with a MANAGER message on the root window
+        conn.change_window_attributes(
+            conn.setup().roots[screen_num].root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )?;
+        let manager = watch_manager(&conn, selection)?;
+        Ok((atoms, selection, manager))
+    };
+    let Ok((atoms, selection, mut manager)) = setup() else {
+        return false;
+    };
+
+    let running = manager != x11rb::NONE;
+    std::thread::spawn(move || {
+        let mut running = running;
+        while let Ok(event) = conn.wait_for_event() {
+            let changed = match event {
+                Event::ClientMessage(e) => {
+                    e.type_ == atoms.MANAGER && e.data.as_data32()[1] == selection
+                }
+                // trays release the selection by destroying their window
+                Event::DestroyNotify(e) => e.window == manager,
+                _ => false,
+            };
+            if !changed {
+                continue;
+            }
+
+            manager = watch_manager(&conn, selection).unwrap_or(x11rb::NONE);
+            if running != (manager != x11rb::NONE) {
+                running = !running;
+                on_change(running);
+            }
+        }
+    });
+
+    running
+}
+
+/// Returns the owner of the tray selection, and listens for it being destroyed.
+///
+/// The server is grabbed so the owner can't go away in between, as the spec recommends.
+fn watch_manager(conn: &RustConnection, selection: Atom) -> Result<Window, ReplyOrIdError> {
+    conn.grab_server()?;
+    let manager = conn.get_selection_owner(selection)?.reply()?.owner;
+    if manager != x11rb::NONE {
+        conn.change_window_attributes(
+            manager,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )?;
+    }
+    conn.ungrab_server()?;
+    conn.flush()?;
+    Ok(manager)
+}
+
+/// The `_NET_SYSTEM_TRAY_S{n}` selection, owned by the tray of screen `n`.
+fn tray_selection(conn: &RustConnection, screen_num: usize) -> Result<Atom, ReplyOrIdError> {
+    Ok(conn
+        .intern_atom(false, format!("_NET_SYSTEM_TRAY_S{screen_num}").as_bytes())?
+        .reply()?
+        .atom)
+}
+
+/// A tray icon docked in an XEmbed system tray.
+pub(crate) struct XEmbedIcon {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    id: TrayIconId,
+    internal_id: u32,
+    conn: RustConnection,
+    atoms: Atoms,
+    /// The `_NET_SYSTEM_TRAY_S{n}` selection owned by the tray.
+    tray_selection: Atom,
+    root: Window,
+    window: Window,
+    gc: Gcontext,
+    depth: u8,
+    /// Whether the window has an alpha channel, otherwise the icon is blended
+    /// with the background of the tray.
+    argb: bool,
+    /// Pixels are only painted for the common 32 bits per pixel formats.
+    can_paint: bool,
+    byte_order: ImageOrder,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    icon: Option<PlatformIcon>,
+    width: u16,
+    height: u16,
+    mapped: bool,
+}
+
+impl XEmbedIcon {
+    /// Docks a window in the system tray of the X11 display.
+    ///
+    /// Returns `None` when there is no X11 display or no system tray running on it.
+    pub(crate) fn new(
+        id: TrayIconId,
+        internal_id: u32,
+        icon: Option<&PlatformIcon>,
+        title: &str,
+    ) -> Result<Option<Self>, ReplyOrIdError> {
+        let Ok((conn, screen_num)) = x11rb::connect(None) else {
+            return Ok(None);
+        };
+
+        let atoms = Atoms::new(&conn)?.reply()?;
+        let tray_selection = tray_selection(&conn, screen_num)?;
+        let manager = conn.get_selection_owner(tray_selection)?.reply()?.owner;
+        if manager == x11rb::NONE {
+            return Ok(None);
+        }
+
+        let screen = &conn.setup().roots[screen_num];
+        let root = screen.root;
+
+        // trays supporting transparency advertise an ARGB visual for their icons
+        let tray_visual = conn
+            .get_property(
+                false,
+                manager,
+                atoms._NET_SYSTEM_TRAY_VISUAL,
+                AtomEnum::VISUALID,
+                0,
+                1,
+            )?
+            .reply()?
+            .value32()
+            .and_then(|mut values| values.next())
+            .filter(|visual| {
+                screen
+                    .allowed_depths
+                    .iter()
+                    .any(|d| d.depth == 32 && d.visuals.iter().any(|v| v.visual_id == *visual))
+            });
+
+        let window = conn.generate_id()?;
+        let event_mask = EventMask::EXPOSURE
+            | EventMask::STRUCTURE_NOTIFY
+            | EventMask::BUTTON_PRESS
+            | EventMask::BUTTON_RELEASE
+            | EventMask::ENTER_WINDOW
+            | EventMask::LEAVE_WINDOW
+            | EventMask::POINTER_MOTION;
+        let depth = match tray_visual {
+            Some(visual) => {
+                let colormap = conn.generate_id()?;
+                conn.create_colormap(ColormapAlloc::NONE, colormap, root, visual)?;
+                conn.create_window(
+                    32,
+                    window,
+                    root,
+                    0,
+                    0,
+                    DEFAULT_SIZE,
+                    DEFAULT_SIZE,
+                    0,
+                    WindowClass::INPUT_OUTPUT,
+                    visual,
+                    &CreateWindowAux::new()
+                        .event_mask(event_mask)
+                        .background_pixel(0)
+                        .border_pixel(0)
+                        .colormap(colormap),
+                )?;
+                32
+            }
+            None => {
+                conn.create_window(
+                    x11rb::COPY_DEPTH_FROM_PARENT,
+                    window,
+                    root,
+                    0,
+                    0,
+                    DEFAULT_SIZE,
+                    DEFAULT_SIZE,
+                    0,
+                    WindowClass::INPUT_OUTPUT,
+                    x11rb::COPY_FROM_PARENT,
+                    &CreateWindowAux::new()
+                        .event_mask(event_mask)
+                        .background_pixmap(BackPixmap::PARENT_RELATIVE),
+                )?;
+                screen.root_depth
+            }
+        };
+
+        let gc = conn.generate_id()?;
+        conn.create_gc(gc, window, &CreateGCAux::new())?;
+
+        // the tray announces itself with a MANAGER message on the root window when it (re)starts
+        conn.change_window_attributes(
+            root,
+            &ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY),
+        )?;
+
+        let can_paint = conn
+            .setup()
+            .pixmap_formats
+            .iter()
+            .any(|f| f.depth == depth && f.bits_per_pixel == 32);
+        let byte_order = conn.setup().image_byte_order;
+
+        let inner = Arc::new(Inner {
+            id,
+            internal_id,
+            conn,
+            atoms,
+            tray_selection,
+            root,
+            window,
+            gc,
+            depth,
+            argb: tray_visual.is_some(),
+            can_paint,
+            byte_order,
+            state: Mutex::new(State {
+                icon: icon.cloned(),
+                width: DEFAULT_SIZE,
+                height: DEFAULT_SIZE,
+                mapped: false,
+            }),
+        });
+
+        inner.set_title(title)?;
+        inner.set_xembed_info(true)?;
+        inner.dock()?;
+
+        let thread_inner = inner.clone();
+        std::thread::spawn(move || thread_inner.run());
+
+        Ok(Some(Self { inner }))
+    }
+
+    pub(crate) fn set_icon(&self, icon: Option<&PlatformIcon>) -> Result<(), ReplyOrIdError> {
+        let mapped = {
+            let mut state = self.inner.state.lock().unwrap();
+            state.icon = icon.cloned();
+            state.mapped
+        };
+        if mapped {
+            self.inner.paint()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_title(&self, title: &str) -> Result<(), ReplyOrIdError> {
+        self.inner.set_title(title)
+    }
+
+    /// The tray maps and unmaps the window following the `XEMBED_MAPPED` flag.
+    pub(crate) fn set_visible(&self, visible: bool) -> Result<(), ReplyOrIdError> {
+        self.inner.set_xembed_info(visible)
+    }
+
+    pub(crate) fn rect(&self) -> Option<Rect> {
+        self.inner.rect().ok()
+    }
+}
+
+impl Drop for XEmbedIcon {
+    fn drop(&mut self) {
+        // the event thread stops once it sees the window is destroyed
+        let _ = self.inner.conn.destroy_window(self.inner.window);
+        let _ = self.inner.conn.flush();
+    }
+}
+
+/// The XEmbed fallback of a tray icon, docked only while no StatusNotifierItem host is around.
+///
+/// It keeps what the icon should show so it can be docked again at any time.
+pub(crate) struct XEmbedFallback {
+    id: TrayIconId,
+    internal_id: u32,
+    icon: Option<PlatformIcon>,
+    title: String,
+    visible: bool,
+    docked: Option<XEmbedIcon>,
+}
+
+impl XEmbedFallback {
+    pub(crate) fn new(id: TrayIconId, internal_id: u32, title: String) -> Self {
+        Self {
+            id,
+            internal_id,
+            icon: None,
+            title,
+            visible: true,
+            docked: None,
+        }
+    }
+
+    /// Docks the icon in the XEmbed tray when there is no StatusNotifierItem host,
+    /// and removes it from the tray otherwise.
+    ///
+    /// Returns whether the icon was docked.
+    pub(crate) fn update(&mut self, hosts: Hosts) -> Result<bool, ReplyOrIdError> {
+        if hosts.sni || !hosts.xembed {
+            self.docked = None;
+            return Ok(false);
+        }
+        if self.docked.is_some() {
+            return Ok(false);
+        }
+
+        self.docked = XEmbedIcon::new(
+            self.id.clone(),
+            self.internal_id,
+            self.icon.as_ref(),
+            &self.title,
+        )?;
+        match &self.docked {
+            Some(docked) => {
+                if !self.visible {
+                    docked.set_visible(false)?;
+                }
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    pub(crate) fn set_icon(&mut self, icon: Option<&PlatformIcon>) -> Result<(), ReplyOrIdError> {
+        self.icon = icon.cloned();
+        match &self.docked {
+            Some(docked) => docked.set_icon(icon),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn set_title(&mut self, title: &str) -> Result<(), ReplyOrIdError> {
+        title.clone_into(&mut self.title);
+        match &self.docked {
+            Some(docked) => docked.set_title(title),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn set_visible(&mut self, visible: bool) -> Result<(), ReplyOrIdError> {
+        self.visible = visible;
+        match &self.docked {
+            Some(docked) => docked.set_visible(visible),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn rect(&self) -> Option<Rect> {
+        self.docked.as_ref().and_then(|docked| docked.rect())
+    }
+}
+
+impl Inner {
+    /// Asks the current tray, if any, to embed the window.
+    fn dock(&self) -> Result<(), ReplyOrIdError> {
+        let manager = self
+            .conn
+            .get_selection_owner(self.tray_selection)?
+            .reply()?
+            .owner;
+        if manager == x11rb::NONE {
+            return Ok(());
+        }
+
+        let event = ClientMessageEvent::new(
+            32,
+            manager,
+            self.atoms._NET_SYSTEM_TRAY_OPCODE,
+            [
+                x11rb::CURRENT_TIME,
+                SYSTEM_TRAY_REQUEST_DOCK,
+                self.window,
+                0,
+                0,
+            ],
+        );
+        self.conn
+            .send_event(false, manager, EventMask::NO_EVENT, event)?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn set_title(&self, title: &str) -> Result<(), ReplyOrIdError> {
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            self.window,
+            AtomEnum::WM_NAME,
+            AtomEnum::STRING,
+            title.as_bytes(),
+        )?;
+        self.conn.change_property8(
+            PropMode::REPLACE,
+            self.window,
+            self.atoms._NET_WM_NAME,
+            self.atoms.UTF8_STRING,
+            title.as_bytes(),
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn set_xembed_info(&self, mapped: bool) -> Result<(), ReplyOrIdError> {
+        let flags = if mapped { XEMBED_MAPPED } else { 0 };
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.window,
+            self.atoms._XEMBED_INFO,
+            self.atoms._XEMBED_INFO,
+            &[0, flags],
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn rect(&self) -> Result<Rect, ReplyOrIdError> {
+        let position = self
+            .conn
+            .translate_coordinates(self.window, self.root, 0, 0)?
+            .reply()?;
+        let state = self.state.lock().unwrap();
+        Ok(Rect {
+            size: PhysicalSize::new(state.width as u32, state.height as u32),
+            position: PhysicalPosition::new(position.dst_x as f64, position.dst_y as f64),
+        })
+    }
+
+    /// Paints the icon centered in the window, scaled down if it doesn't fit.
+    fn paint(&self) -> Result<(), ReplyOrIdError> {
+        let state = self.state.lock().unwrap();
+
+        self.conn.clear_area(false, self.window, 0, 0, 0, 0)?;
+
+        let Some(icon) = state.icon.as_ref().filter(|_| self.can_paint) else {
+            self.conn.flush()?;
+            return Ok(());
+        };
+        let (width, height) = (state.width as u32, state.height as u32);
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let icon = icon.image_for_size(width.min(height));
+
+        let icon = if icon.width > width || icon.height > height {
+            let scale = f64::min(
+                width as f64 / icon.width as f64,
+                height as f64 / icon.height as f64,
+            );
+            Cow::Owned(icon.downscaled(
+                (icon.width as f64 * scale) as u32,
+                (icon.height as f64 * scale) as u32,
+            ))
+        } else {
+            icon
+        };
+        let x = ((width - icon.width) / 2) as i16;
+        let y = ((height - icon.height) / 2) as i16;
+
+        // without an alpha channel, blend with what the tray painted behind the window
+        let mut data = if self.argb {
+            vec![0; icon.rgba.len()]
+        } else {
+            self.conn
+                .get_image(
+                    ImageFormat::Z_PIXMAP,
+                    self.window,
+                    x,
+                    y,
+                    icon.width as u16,
+                    icon.height as u16,
+                    !0,
+                )?
+                .reply()?
+                .data
+        };
+
+        for (src, dst) in icon.rgba.chunks_exact(4).zip(data.chunks_exact_mut(4)) {
+            let background = self.read_pixel(dst);
+            let pixel = blend([src[0], src[1], src[2], src[3]], background, self.argb);
+            self.write_pixel(dst, pixel);
+        }
+
+        self.conn.put_image(
+            ImageFormat::Z_PIXMAP,
+            self.window,
+            self.gc,
+            icon.width as u16,
+            icon.height as u16,
+            x,
+            y,
+            0,
+            self.depth,
+            &data,
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn read_pixel(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.byte_order == ImageOrder::LSB_FIRST {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    }
+
+    fn write_pixel(&self, bytes: &mut [u8], pixel: u32) {
+        let pixel = if self.byte_order == ImageOrder::LSB_FIRST {
+            pixel.to_le_bytes()
+        } else {
+            pixel.to_be_bytes()
+        };
+        bytes.copy_from_slice(&pixel);
+    }
+
+    fn run(&self) {
+        while let Ok(event) = self.conn.wait_for_event() {
+            match event {
+                Event::Expose(e) if e.window == self.window && e.count == 0 => {
+                    let _ = self.paint();
+                }
+                Event::ConfigureNotify(e) if e.window == self.window => {
+                    let mut state = self.state.lock().unwrap();
+                    state.width = e.width;
+                    state.height = e.height;
+                }
+                Event::MapNotify(e) if e.window == self.window => {
+                    self.state.lock().unwrap().mapped = true;
+                }
+                Event::UnmapNotify(e) if e.window == self.window => {
+                    self.state.lock().unwrap().mapped = false;
+                }
+                Event::EnterNotify(e) if e.event == self.window => {
+                    self.send_pointer_event(e.root_x, e.root_y, |id, position, rect| {
+                        TrayIconEvent::Enter { id, position, rect }
+                    });
+                }
+                Event::MotionNotify(e) if e.event == self.window => {
+                    self.send_pointer_event(e.root_x, e.root_y, |id, position, rect| {
+                        TrayIconEvent::Move { id, position, rect }
+                    });
+                }
+                Event::LeaveNotify(e) if e.event == self.window => {
+                    self.send_pointer_event(e.root_x, e.root_y, |id, position, rect| {
+                        TrayIconEvent::Leave { id, position, rect }
+                    });
+                }
+                Event::ButtonPress(e) if e.event == self.window => {
+                    self.on_button(e, MouseButtonState::Down)
+                }
+                Event::ButtonRelease(e) if e.event == self.window => {
+                    self.on_button(e, MouseButtonState::Up)
+                }
+                // a new tray took the selection, most likely the panel restarted
+                Event::ClientMessage(e)
+                    if e.type_ == self.atoms.MANAGER
+                        && e.data.as_data32()[1] == self.tray_selection
+                        && self.dock().is_ok() =>
+                {
+                    TrayIconEvent::send(TrayIconEvent::Reregistered {
+                        id: self.id.clone(),
+                    });
+                }
+                Event::DestroyNotify(e) if e.window == self.window => break,
+                _ => {}
+            }
+        }
+    }
+
+    fn send_pointer_event(
+        &self,
+        x: i16,
+        y: i16,
+        event: impl FnOnce(TrayIconId, PhysicalPosition<f64>, Rect) -> TrayIconEvent,
+    ) {
+        let rect = self.rect().unwrap_or_default();
+        let position = PhysicalPosition::new(x as f64, y as f64);
+        TrayIconEvent::send(event(self.id.clone(), position, rect));
+    }
+
+    fn on_button(&self, e: ButtonPressEvent, button_state: MouseButtonState) {
+        let button = match e.detail {
+            1 => MouseButton::Left,
+            2 => MouseButton::Middle,
+            3 => MouseButton::Right,
+            // the wheel is reported as buttons 4 to 7, only presses are meaningful
+            4..=7 => {
+                if let Some((delta, orientation)) =
+                    wheel(e.detail).filter(|_| button_state == MouseButtonState::Down)
+                {
+                    self.send_pointer_event(e.root_x, e.root_y, |id, position, rect| {
+                        TrayIconEvent::Scroll {
+                            id,
+                            delta,
+                            orientation,
+                            position,
+                            rect,
+                        }
+                    });
+                }
+                return;
+            }
+            _ => return,
+        };
+
+        self.send_pointer_event(e.root_x, e.root_y, |id, position, rect| {
+            TrayIconEvent::Click {
+                id,
+                position,
+                rect,
+                button,
+                button_state,
+            }
+        });
+
+        if button == MouseButton::Right && button_state == MouseButtonState::Up {
+            super::menu::show(self.internal_id);
+        }
+    }
+}
+
+/// The scroll delta and orientation of wheel buttons, one step per press,
+/// positive when scrolling down or right.
+fn wheel(button: u8) -> Option<(i32, ScrollOrientation)> {
+    match button {
+        4 => Some((-1, ScrollOrientation::Vertical)),
+        5 => Some((1, ScrollOrientation::Vertical)),
+        6 => Some((-1, ScrollOrientation::Horizontal)),
+        7 => Some((1, ScrollOrientation::Horizontal)),
+        _ => None,
+    }
+}
+
+/// Converts an RGBA pixel to a `0xAARRGGBB` pixel value.
+///
+/// ARGB visuals expect premultiplied alpha, otherwise the pixel is composited
+/// over the `0x00RRGGBB` `background`.
+fn blend([r, g, b, a]: [u8; 4], background: u32, argb: bool) -> u32 {
+    let a = a as u32;
+    let channel = |src: u8, shift: u32| {
+        let src = src as u32 * a;
+        if argb {
+            src / 255
+        } else {
+            (src + ((background >> shift) & 0xff) * (255 - a)) / 255
+        }
+    };
+
+    let alpha = if argb { a } else { 0xff };
+    (alpha << 24) | (channel(r, 16) << 16) | (channel(g, 8) << 8) | channel(b, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    //! These tests need an X server, run them with `xvfb-run cargo test -- --include-ignored`.
+
+    use std::{
+        sync::MutexGuard,
+        time::{Duration, Instant},
+    };
+
+    use x11rb::{
+        connection::Connection,
+        protocol::{
+            xproto::{
+                ButtonPressEvent, ConfigureWindowAux, ConnectionExt, CreateWindowAux,
+                EnterNotifyEvent, KeyButMask, Motion, MotionNotifyEvent, NotifyDetail, NotifyMode,
+                WindowClass, BUTTON_PRESS_EVENT, BUTTON_RELEASE_EVENT, ENTER_NOTIFY_EVENT,
+                LEAVE_NOTIFY_EVENT, MOTION_NOTIFY_EVENT,
+            },
+            Event,
+        },
+    };
+
+    use super::super::tests::recv_events;
+    use super::*;
+
+    /// Only one tray can own the selection, so tests using it run one at a time.
+    static TRAY_LOCK: Mutex<()> = Mutex::new(());
+
+    /// A minimal tray manager, it only owns the selection and embeds what it is asked to.
+    struct StubTray {
+        _lock: MutexGuard<'static, ()>,
+        conn: RustConnection,
+        root: Window,
+        window: Window,
+        opcode: Atom,
+    }
+
+    impl StubTray {
+        /// Places the tray at 10,20 on the screen.
+        fn new() -> Self {
+            let _lock = TRAY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let (conn, screen_num) = x11rb::connect(None)
+                .expect("these tests need an X server, run them with `xvfb-run`");
+            let screen = &conn.setup().roots[screen_num];
+            let root = screen.root;
+
+            let window = conn.generate_id().unwrap();
+            conn.create_window(
+                x11rb::COPY_DEPTH_FROM_PARENT,
+                window,
+                root,
+                10,
+                20,
+                100,
+                30,
+                0,
+                WindowClass::INPUT_OUTPUT,
+                x11rb::COPY_FROM_PARENT,
+                &CreateWindowAux::new().background_pixel(screen.black_pixel),
+            )
+            .unwrap();
+            conn.map_window(window).unwrap();
+            let selection = tray_selection(&conn, screen_num).unwrap();
+            let opcode = Atoms::new(&conn)
+                .unwrap()
+                .reply()
+                .unwrap()
+                ._NET_SYSTEM_TRAY_OPCODE;
+            conn.set_selection_owner(window, selection, x11rb::CURRENT_TIME)
+                .unwrap();
+            conn.flush().unwrap();
+
+            Self {
+                _lock,
+                conn,
+                root,
+                window,
+                opcode,
+            }
+        }
+
+        /// Waits for a dock request and embeds the window at 30,4 in the tray, 22 pixels wide.
+        fn embed(&self) -> Window {
+            let window = loop {
+                if let Event::ClientMessage(e) = self.conn.wait_for_event().unwrap() {
+                    let data = e.data.as_data32();
+                    if e.type_ == self.opcode && data[1] == SYSTEM_TRAY_REQUEST_DOCK {
+                        break data[2];
+                    }
+                }
+            };
+
+            self.conn
+                .reparent_window(window, self.window, 30, 4)
+                .unwrap();
+            self.conn
+                .configure_window(window, &ConfigureWindowAux::new().width(22).height(22))
+                .unwrap();
+            self.conn.map_window(window).unwrap();
+            self.conn.sync().unwrap();
+            window
+        }
+    }
+
+    fn wait_until(mut f: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !f() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(f(), "timed out");
+    }
+
+    #[test]
+    fn maps_wheel_buttons_to_scroll_deltas() {
+        // same convention as StatusNotifierItem hosts once their delta is flipped
+        assert_eq!(wheel(4), Some((-1, ScrollOrientation::Vertical)));
+        assert_eq!(wheel(5), Some((1, ScrollOrientation::Vertical)));
+        assert_eq!(wheel(6), Some((-1, ScrollOrientation::Horizontal)));
+        assert_eq!(wheel(7), Some((1, ScrollOrientation::Horizontal)));
+        assert_eq!(wheel(1), None);
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn docks_in_tray_and_reports_geometry() {
+        let tray = StubTray::new();
+        let icon = XEmbedIcon::new(TrayIconId::new("xembed"), 0, None, "title")
+            .unwrap()
+            .expect("the tray should be found");
+
+        let window = tray.embed();
+        assert_eq!(window, icon.inner.window);
+
+        let expected = Rect {
+            size: PhysicalSize::new(22, 22),
+            position: PhysicalPosition::new(40.0, 24.0),
+        };
+        wait_until(|| icon.rect() == Some(expected));
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn docks_only_without_status_notifier_host() {
+        let tray = StubTray::new();
+        let mut fallback =
+            XEmbedFallback::new(TrayIconId::new("xembed-fallback"), 0, "title".into());
+        fallback.set_visible(false).unwrap();
+
+        let sni = Hosts {
+            sni: true,
+            xembed: true,
+        };
+        assert!(!fallback.update(sni).unwrap());
+        assert!(fallback.docked.is_none());
+
+        let xembed = Hosts {
+            sni: false,
+            xembed: true,
+        };
+        assert!(fallback.update(xembed).unwrap());
+        let window = tray.embed();
+        let docked = fallback.docked.as_ref().unwrap();
+        assert_eq!(window, docked.inner.window);
+        // hidden icons stay hidden once docked
+        wait_until(|| {
+            let info = tray
+                .conn
+                .get_property(
+                    false,
+                    window,
+                    docked.inner.atoms._XEMBED_INFO,
+                    docked.inner.atoms._XEMBED_INFO,
+                    0,
+                    2,
+                )
+                .unwrap()
+                .reply()
+                .unwrap();
+            info.value32()
+                .and_then(|mut values| values.nth(1))
+                .is_some_and(|flags| flags & XEMBED_MAPPED == 0)
+        });
+        // already docked
+        assert!(!fallback.update(xembed).unwrap());
+
+        assert!(!fallback.update(sni).unwrap());
+        assert!(fallback.docked.is_none());
+        assert!(fallback.rect().is_none());
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn paints_the_icon() {
+        let tray = StubTray::new();
+        let red = PlatformIcon::from_rgba([255, 0, 0, 255].repeat(4 * 4), 4, 4).unwrap();
+        let icon = XEmbedIcon::new(TrayIconId::new("xembed-paint"), 0, Some(&red), "title")
+            .unwrap()
+            .expect("the tray should be found");
+        let window = tray.embed();
+
+        // centered in the 22x22 window, without an alpha channel since the stub tray has no ARGB visual
+        let pixel = |x, y| {
+            let image = tray
+                .conn
+                .get_image(ImageFormat::Z_PIXMAP, window, x, y, 1, 1, !0)
+                .unwrap()
+                .reply()
+                .unwrap();
+            icon.inner.read_pixel(&image.data) & 0xffffff
+        };
+        wait_until(|| pixel(9, 9) == 0xff0000 && pixel(12, 12) == 0xff0000);
+
+        let blue = PlatformIcon::from_rgba([0, 0, 255, 255].repeat(2 * 2), 2, 2).unwrap();
+        icon.set_icon(Some(&blue)).unwrap();
+        wait_until(|| pixel(10, 10) == 0x0000ff && pixel(9, 9) == 0);
+    }
+
+    #[test]
+    #[ignore = "needs an X server"]
+    fn emits_pointer_events_for_its_window_only() {
+        let tray = StubTray::new();
+        let id = TrayIconId::new("xembed-events");
+        let icon = XEmbedIcon::new(id.clone(), 0, None, "title")
+            .unwrap()
+            .expect("the tray should be found");
+        let window = tray.embed();
+        wait_until(|| icon.rect().is_some_and(|rect| rect.size.width == 22));
+
+        // the icon also listens to the root window, whose events must be ignored
+        let send = |destination: Window, mask: EventMask, event: [u8; 32]| {
+            tray.conn
+                .send_event(false, destination, mask, event)
+                .unwrap();
+            tray.conn.flush().unwrap();
+        };
+        let crossing = |response_type, event, x| EnterNotifyEvent {
+            response_type,
+            detail: NotifyDetail::ANCESTOR,
+            sequence: 0,
+            time: x11rb::CURRENT_TIME,
+            root: tray.root,
+            event,
+            child: x11rb::NONE,
+            root_x: x,
+            root_y: 30,
+            event_x: 0,
+            event_y: 0,
+            state: KeyButMask::from(0u16),
+            mode: NotifyMode::NORMAL,
+            same_screen_focus: 1,
+        };
+        let button = |response_type, event, detail| ButtonPressEvent {
+            response_type,
+            detail,
+            sequence: 0,
+            time: x11rb::CURRENT_TIME,
+            root: tray.root,
+            event,
+            child: x11rb::NONE,
+            root_x: 45,
+            root_y: 30,
+            event_x: 5,
+            event_y: 6,
+            state: KeyButMask::from(0u16),
+            same_screen: true,
+        };
+
+        send(
+            tray.root,
+            EventMask::STRUCTURE_NOTIFY,
+            crossing(ENTER_NOTIFY_EVENT, tray.root, 1).into(),
+        );
+        send(
+            tray.root,
+            EventMask::STRUCTURE_NOTIFY,
+            button(BUTTON_PRESS_EVENT, tray.root, 1).into(),
+        );
+        send(
+            window,
+            EventMask::ENTER_WINDOW,
+            crossing(ENTER_NOTIFY_EVENT, window, 41).into(),
+        );
+        let motion = MotionNotifyEvent {
+            response_type: MOTION_NOTIFY_EVENT,
+            detail: Motion::NORMAL,
+            sequence: 0,
+            time: x11rb::CURRENT_TIME,
+            root: tray.root,
+            event: window,
+            child: x11rb::NONE,
+            root_x: 42,
+            root_y: 30,
+            event_x: 2,
+            event_y: 6,
+            state: KeyButMask::from(0u16),
+            same_screen: true,
+        };
+        send(window, EventMask::POINTER_MOTION, motion.into());
+        send(
+            window,
+            EventMask::BUTTON_PRESS,
+            button(BUTTON_PRESS_EVENT, window, 1).into(),
+        );
+        send(
+            window,
+            EventMask::BUTTON_RELEASE,
+            button(BUTTON_RELEASE_EVENT, window, 1).into(),
+        );
+        send(
+            window,
+            EventMask::LEAVE_WINDOW,
+            crossing(LEAVE_NOTIFY_EVENT, window, 43).into(),
+        );
+
+        let rect = icon.rect().unwrap();
+        let events = recv_events(&id, 5)
+            .into_iter()
+            .map(|event| match event {
+                TrayIconEvent::Enter {
+                    position, rect: r, ..
+                } => ("enter", position.x, r),
+                TrayIconEvent::Move {
+                    position, rect: r, ..
+                } => ("move", position.x, r),
+                TrayIconEvent::Leave {
+                    position, rect: r, ..
+                } => ("leave", position.x, r),
+                TrayIconEvent::Click {
+                    position,
+                    rect: r,
+                    button: MouseButton::Left,
+                    button_state: MouseButtonState::Down,
+                    ..
+                } => ("press", position.x, r),
+                TrayIconEvent::Click {
+                    position,
+                    rect: r,
+                    button: MouseButton::Left,
+                    button_state: MouseButtonState::Up,
+                    ..
+                } => ("release", position.x, r),
+                event => panic!("unexpected event {event:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            [
+                ("enter", 41.0, rect),
+                ("move", 42.0, rect),
+                ("press", 45.0, rect),
+                ("release", 45.0, rect),
+                ("leave", 43.0, rect),
+            ]
+        );
+    }
+}
@@ -2,8 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0
 // SPDX-License-Identifier: MIT
 
-use crate::icon::{BadIcon, RgbaIcon};
-use std::io::Cursor;
+#[cfg(feature = "svg")]
+use crate::icon::SvgIcon;
+use crate::icon::{best_for_size, BadIcon, RgbaIcon};
+use std::borrow::Cow;
+
+/// Twice the height of status bar icons, see `set_icon_for_ns_status_item_button`.
+const RETINA_ICON_SIZE: u32 = 36;
 
 #[derive(Debug, Clone)]
 pub struct PlatformIcon(RgbaIcon);
@@ -13,23 +18,30 @@ impl PlatformIcon {
         Ok(PlatformIcon(RgbaIcon::from_rgba(rgba, width, height)?))
     }
 
-    pub fn get_size(&self) -> (u32, u32) {
-        (self.0.width, self.0.height)
+    /// Renders the icon at twice the status bar icon height, so it stays sharp on Retina displays.
+    /// The status bar keeps the aspect ratio of the image.
+    #[cfg(feature = "svg")]
+    pub fn from_svg(svg: SvgIcon) -> Result<Self, BadIcon> {
+        Ok(PlatformIcon(svg.rasterize(RETINA_ICON_SIZE)))
     }
 
-    pub fn to_png(&self) -> crate::Result<Vec<u8>> {
-        let mut png = Vec::new();
+    /// Uses the size closest to the status bar icon height on Retina displays,
+    /// AppKit scales it to the status bar.
+    pub fn from_sizes(images: Vec<RgbaIcon>) -> Result<Self, BadIcon> {
+        let icon = best_for_size(&images, RETINA_ICON_SIZE).ok_or(BadIcon::NoImages)?;
+        Ok(PlatformIcon(icon.clone()))
+    }
 
-        {
-            let mut encoder =
-                png::Encoder::new(Cursor::new(&mut png), self.0.width as _, self.0.height as _);
-            encoder.set_color(png::ColorType::Rgba);
-            encoder.set_depth(png::BitDepth::Eight);
+    pub fn rgba_icon(&self) -> Option<&RgbaIcon> {
+        Some(&self.0)
+    }
 
-            let mut writer = encoder.write_header()?;
-            writer.write_image_data(&self.0.rgba)?;
-        }
+    pub fn to_rgba_icon(&self) -> Result<Cow<'_, RgbaIcon>, BadIcon> {
+        Ok(Cow::Borrowed(&self.0))
+    }
 
-        Ok(png)
+    /// The status bar only uses one size.
+    pub fn exported_images(&self) -> Vec<Cow<'_, RgbaIcon>> {
+        vec![Cow::Borrowed(&self.0)]
     }
 }
@@ -3,8 +3,18 @@
 // SPDX-License-Identifier: MIT
 
 mod icon;
-use std::cell::{Cell, RefCell};
+use std::{
+    cell::{Cell, RefCell},
+    ffi::c_void,
+    time::Duration,
+};
 
+use core_foundation::{
+    date::CFDate,
+    runloop::{
+        kCFRunLoopCommonModes, CFRunLoop, CFRunLoopTimer, CFRunLoopTimerContext, CFRunLoopTimerRef,
+    },
+};
 use core_graphics::display::CGDisplay;
 use objc2::rc::Retained;
 use objc2::{declare_class, msg_send, msg_send_id, mutability, ClassType, DeclaredClass};
@@ -26,6 +36,10 @@ pub struct TrayIcon {
     tray_target: Option<Retained<TrayTarget>>,
     id: TrayIconId,
     attrs: TrayIconAttributes,
+    attention: bool,
+    overlay_icon: Option<Icon>,
+    /// The frame of the running animation, shown instead of `attrs.icon`.
+    animation_frame: Option<Icon>,
     mtm: MainThreadMarker,
 }
 
@@ -39,6 +53,9 @@ impl TrayIcon {
             tray_target: Some(tray_target),
             id,
             attrs,
+            attention: false,
+            overlay_icon: None,
+            animation_frame: None,
             mtm,
         };
 
@@ -112,12 +129,76 @@ impl TrayIcon {
     }
 
     pub fn set_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.animation_frame = None;
         if let (Some(ns_status_item), Some(tray_target)) = (&self.ns_status_item, &self.tray_target)
         {
-            set_icon_for_ns_status_item_button(ns_status_item, icon.clone(), false, self.mtm)?;
-            tray_target.update_dimensions();
+            if !self.icon_is_modified() {
+                set_icon_for_ns_status_item_button(ns_status_item, icon.clone(), false, self.mtm)?;
+                tray_target.update_dimensions();
+            }
         }
         self.attrs.icon = icon;
+        if self.icon_is_modified() {
+            self.update_icon()?;
+        }
+        Ok(())
+    }
+
+    pub fn set_attention_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.attrs.attention_icon = icon;
+        if self.attention {
+            self.update_icon()?;
+        }
+        Ok(())
+    }
+
+    /// Status items have no overlays, so the overlay is drawn onto the icon.
+    pub fn set_overlay_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.overlay_icon = icon;
+        self.update_icon()
+    }
+
+    /// Status items have no attention state, so the icon itself is swapped.
+    pub fn set_attention(&mut self, attention: bool) -> crate::Result<()> {
+        self.attention = attention;
+        self.update_icon()
+    }
+
+    pub fn set_animation_frame(&mut self, frame: Option<Icon>) -> crate::Result<()> {
+        self.animation_frame = frame;
+        self.update_icon()
+    }
+
+    /// Whether the shown icon differs from `attrs.icon`.
+    fn icon_is_modified(&self) -> bool {
+        (self.attention && self.attrs.attention_icon.is_some())
+            || self.overlay_icon.is_some()
+            || self.animation_frame.is_some()
+    }
+
+    /// Shows the attention icon or the regular icon depending on the current state,
+    /// with the overlay drawn onto it.
+    fn update_icon(&self) -> crate::Result<()> {
+        if let (Some(ns_status_item), Some(tray_target)) = (&self.ns_status_item, &self.tray_target)
+        {
+            let icon = match &self.attrs.attention_icon {
+                Some(attention_icon) if self.attention => Some(attention_icon),
+                _ => self.animation_frame.as_ref().or(self.attrs.icon.as_ref()),
+            };
+            let icon = match (icon, &self.overlay_icon) {
+                (Some(icon), Some(overlay)) => {
+                    Some(icon.with_overlay(overlay).unwrap_or_else(|| icon.clone()))
+                }
+                (icon, _) => icon.cloned(),
+            };
+            set_icon_for_ns_status_item_button(
+                ns_status_item,
+                icon,
+                self.attrs.icon_is_template,
+                self.mtm,
+            )?;
+            tray_target.update_dimensions();
+        }
         Ok(())
     }
 
@@ -195,6 +276,9 @@ impl TrayIcon {
                 let (ns_status_item, tray_target) = Self::create(&self.id, &self.attrs, self.mtm)?;
                 self.ns_status_item = Some(ns_status_item);
                 self.tray_target = Some(tray_target);
+                if self.icon_is_modified() {
+                    self.update_icon()?;
+                }
             }
         } else {
             self.remove();
@@ -221,18 +305,24 @@ impl TrayIcon {
         icon: Option<Icon>,
         is_template: bool,
     ) -> crate::Result<()> {
+        self.animation_frame = None;
         if let (Some(ns_status_item), Some(tray_target)) = (&self.ns_status_item, &self.tray_target)
         {
-            set_icon_for_ns_status_item_button(
-                ns_status_item,
-                icon.clone(),
-                is_template,
-                self.mtm,
-            )?;
-            tray_target.update_dimensions();
+            if !self.icon_is_modified() {
+                set_icon_for_ns_status_item_button(
+                    ns_status_item,
+                    icon.clone(),
+                    is_template,
+                    self.mtm,
+                )?;
+                tray_target.update_dimensions();
+            }
         }
         self.attrs.icon = icon;
         self.attrs.icon_is_template = is_template;
+        if self.icon_is_modified() {
+            self.update_icon()?;
+        }
         Ok(())
     }
 
@@ -259,6 +349,33 @@ impl Drop for TrayIcon {
     }
 }
 
+/// Status items are always shown in the menu bar.
+pub fn host_status() -> crate::HostStatus {
+    crate::HostStatus::Available
+}
+
+/// Runs `f` on the main run loop after `delay`, in the common modes so it also runs
+/// while a menu is open.
+pub(crate) fn run_after(delay: Duration, f: impl FnOnce() + 'static) {
+    extern "C" fn fire(_timer: CFRunLoopTimerRef, info: *mut c_void) {
+        // one-shot timers fire once, so the callback is only taken back once
+        let f = unsafe { Box::from_raw(info as *mut Box<dyn FnOnce()>) };
+        f();
+    }
+
+    let f: Box<Box<dyn FnOnce()>> = Box::new(Box::new(f));
+    let mut context = CFRunLoopTimerContext {
+        version: 0,
+        info: Box::into_raw(f) as *mut c_void,
+        retain: None,
+        release: None,
+        copyDescription: None,
+    };
+    let fire_date = CFDate::now().abs_time() + delay.as_secs_f64();
+    let timer = CFRunLoopTimer::new(fire_date, 0.0, 0, 0, fire, &mut context);
+    CFRunLoop::get_current().add_timer(&timer, unsafe { kCFRunLoopCommonModes });
+}
+
 fn set_icon_for_ns_status_item_button(
     ns_status_item: &NSStatusItem,
     icon: Option<Icon>,
@@ -268,9 +385,13 @@ fn set_icon_for_ns_status_item_button(
     let button = unsafe { ns_status_item.button(mtm).unwrap() };
 
     if let Some(icon) = icon {
-        let png_icon = icon.inner.to_png()?;
+        let rgba = icon
+            .inner
+            .to_rgba_icon()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        let png_icon = rgba.to_png();
 
-        let (width, height) = icon.inner.get_size();
+        let (width, height) = (rgba.width, rgba.height.max(1));
 
         let icon_height: f64 = 18.0;
         let icon_width: f64 = (width as f64) / (height as f64 / icon_height);
@@ -6,7 +6,7 @@
 #[path = "windows/mod.rs"]
 mod platform;
 #[cfg(target_os = "linux")]
-#[path = "gtk/mod.rs"]
+#[path = "linux/mod.rs"]
 mod platform;
 #[cfg(target_os = "macos")]
 #[path = "macos/mod.rs"]
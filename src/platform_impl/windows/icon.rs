@@ -4,12 +4,19 @@
 
 // taken from https://github.com/rust-windowing/winit/blob/92fdf5ba85f920262a61cee4590f4a11ad5738d1/src/platform_impl/windows/icon.rs
 
-use std::{fmt, io, mem, path::Path, sync::Arc};
+use std::{borrow::Cow, fmt, io, mem, path::Path, ptr, sync::Arc};
 
 use windows_sys::{
     core::PCWSTR,
-    Win32::UI::WindowsAndMessaging::{
-        CreateIcon, DestroyIcon, LoadImageW, HICON, IMAGE_ICON, LR_DEFAULTSIZE, LR_LOADFROMFILE,
+    Win32::{
+        Graphics::Gdi::{
+            CreateCompatibleDC, DeleteDC, DeleteObject, GetDIBits, GetObjectW, BITMAP, BITMAPINFO,
+            BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS, HBITMAP, HDC,
+        },
+        UI::WindowsAndMessaging::{
+            CreateIcon, DestroyIcon, GetIconInfo, GetSystemMetrics, LoadImageW, HICON, ICONINFO,
+            IMAGE_ICON, LR_DEFAULTSIZE, LR_LOADFROMFILE, SM_CXSMICON,
+        },
     },
 };
 
@@ -62,6 +69,8 @@ struct RaiiIcon {
 #[derive(Clone)]
 pub(crate) struct WinIcon {
     inner: Arc<RaiiIcon>,
+    /// The pixels the icon was created from, icons loaded by the OS don't have them.
+    rgba: Option<Arc<RgbaIcon>>,
 }
 
 unsafe impl Send for WinIcon {}
@@ -73,13 +82,62 @@ impl WinIcon {
 
     pub fn from_rgba(rgba: Vec<u8>, width: u32, height: u32) -> Result<Self, BadIcon> {
         let rgba_icon = RgbaIcon::from_rgba(rgba, width, height)?;
-        rgba_icon.into_windows_icon()
+        let mut icon = rgba_icon.clone().into_windows_icon()?;
+        icon.rgba = Some(Arc::new(rgba_icon));
+        Ok(icon)
+    }
+
+    /// Renders the icon at the size of notification area icons for the current DPI.
+    #[cfg(feature = "svg")]
+    pub fn from_svg(svg: SvgIcon) -> Result<Self, BadIcon> {
+        let icon = svg.rasterize(small_icon_size());
+        Self::from_rgba(icon.rgba, icon.width, icon.height)
+    }
+
+    /// Uses the size closest to notification area icons for the current DPI, scaled down
+    /// to fit if needed.
+    pub fn from_sizes(images: Vec<RgbaIcon>) -> Result<Self, BadIcon> {
+        let size = small_icon_size();
+        let icon = best_for_size(&images, size).ok_or(BadIcon::NoImages)?;
+        let side = icon.width.max(icon.height);
+        let icon = if side > size {
+            icon.downscaled(icon.width * size / side, icon.height * size / side)
+        } else {
+            icon.clone()
+        };
+        Self::from_rgba(icon.rgba, icon.width, icon.height)
+    }
+
+    pub fn rgba_icon(&self) -> Option<&RgbaIcon> {
+        self.rgba.as_deref()
+    }
+
+    /// Returns the pixels the icon was created from, or reads them back from the icon handle
+    /// for icons loaded by the OS.
+    pub fn to_rgba_icon(&self) -> Result<Cow<'_, RgbaIcon>, BadIcon> {
+        match self.rgba_icon() {
+            Some(icon) => Ok(Cow::Borrowed(icon)),
+            None => read_icon_pixels(self.as_raw_handle())
+                .map(Cow::Owned)
+                .ok_or_else(|| {
+                    BadIcon::OsError(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "failed to read the pixels of the icon handle",
+                    ))
+                }),
+        }
+    }
+
+    /// The notification area only uses one size, there is none if its pixels can't be read.
+    pub fn exported_images(&self) -> Vec<Cow<'_, RgbaIcon>> {
+        self.to_rgba_icon().into_iter().collect()
     }
 
     pub(crate) fn from_handle(handle: HICON) -> Self {
         Self {
             #[allow(clippy::arc_with_non_send_sync)]
             inner: Arc::new(RaiiIcon { handle }),
+            rgba: None,
         }
     }
 
@@ -145,6 +203,102 @@ impl WinIcon {
     }
 }
 
+/// Reads the pixels of `handle` through its color bitmap and AND mask.
+fn read_icon_pixels(handle: HICON) -> Option<RgbaIcon> {
+    let mut info: ICONINFO = unsafe { mem::zeroed() };
+    if unsafe { GetIconInfo(handle, &mut info) } == 0 {
+        return None;
+    }
+
+    let hdc = unsafe { CreateCompatibleDC(ptr::null_mut()) };
+    let pixels = (|| {
+        let mut bitmap: BITMAP = unsafe { mem::zeroed() };
+        // monochrome icons have no color bitmap, their mask holds the AND mask
+        // followed by the XOR mask and is twice the icon height
+        let color = !info.hbmColor.is_null();
+        let size = mem::size_of::<BITMAP>() as i32;
+        let source = if color { info.hbmColor } else { info.hbmMask };
+        if unsafe { GetObjectW(source, size, &mut bitmap as *mut BITMAP as *mut _) } == 0 {
+            return None;
+        }
+        let width = bitmap.bmWidth as u32;
+        let height = if color {
+            bitmap.bmHeight
+        } else {
+            bitmap.bmHeight / 2
+        } as u32;
+
+        let mask = read_bitmap(
+            hdc,
+            info.hbmMask,
+            width,
+            if color { height } else { height * 2 },
+        )?;
+        let (mut bgra, mask) = if color {
+            (read_bitmap(hdc, info.hbmColor, width, height)?, mask)
+        } else {
+            let (mask, xor) = mask.split_at(mask.len() / 2);
+            (xor.to_vec(), mask.to_vec())
+        };
+
+        // icons without alpha channel rely on the AND mask, where white is transparent
+        let has_alpha = color && bgra.chunks_exact(PIXEL_SIZE).any(|p| p[3] != 0);
+        for (pixel, mask) in bgra
+            .chunks_exact_mut(PIXEL_SIZE)
+            .zip(mask.chunks_exact(PIXEL_SIZE))
+        {
+            pixel.swap(0, 2);
+            if !has_alpha {
+                pixel[3] = if mask[0] == 0 { u8::MAX } else { 0 };
+            }
+        }
+        RgbaIcon::from_rgba(bgra, width, height).ok()
+    })();
+
+    unsafe {
+        DeleteDC(hdc);
+        DeleteObject(info.hbmMask);
+        if !info.hbmColor.is_null() {
+            DeleteObject(info.hbmColor);
+        }
+    }
+    pixels
+}
+
+/// Reads `bitmap` as top-down 32bpp BGRA pixels.
+fn read_bitmap(hdc: HDC, bitmap: HBITMAP, width: u32, height: u32) -> Option<Vec<u8>> {
+    let mut info: BITMAPINFO = unsafe { mem::zeroed() };
+    info.bmiHeader = BITMAPINFOHEADER {
+        biSize: mem::size_of::<BITMAPINFOHEADER>() as u32,
+        biWidth: width as i32,
+        // a negative height requests top-down rows
+        biHeight: -(height as i32),
+        biPlanes: 1,
+        biBitCount: (PIXEL_SIZE * 8) as u16,
+        biCompression: BI_RGB,
+        ..unsafe { mem::zeroed() }
+    };
+
+    let mut pixels = vec![0; width as usize * height as usize * PIXEL_SIZE];
+    let lines = unsafe {
+        GetDIBits(
+            hdc,
+            bitmap,
+            0,
+            height,
+            pixels.as_mut_ptr() as _,
+            &mut info,
+            DIB_RGB_COLORS,
+        )
+    };
+    (lines == height as i32).then_some(pixels)
+}
+
+/// The size of notification area icons, scaled for the DPI of the primary monitor.
+fn small_icon_size() -> u32 {
+    unsafe { GetSystemMetrics(SM_CXSMICON) }.max(16) as u32
+}
+
 impl Drop for RaiiIcon {
     fn drop(&mut self) {
         unsafe { DestroyIcon(self.handle) };
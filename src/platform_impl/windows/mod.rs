@@ -4,7 +4,7 @@
 
 mod icon;
 mod util;
-use std::ptr;
+use std::{cell::RefCell, collections::HashMap, ptr, time::Duration};
 
 use once_cell::sync::Lazy;
 use windows_sys::{
@@ -66,6 +66,12 @@ pub struct TrayIcon {
     hwnd: HWND,
     menu: Option<Box<dyn menu::ContextMenu>>,
     internal_id: u32,
+    icon: Option<Icon>,
+    /// The frame of the running animation, shown instead of `icon`.
+    animation_frame: Option<Icon>,
+    attention_icon: Option<Icon>,
+    overlay_icon: Option<Icon>,
+    attention: bool,
 }
 
 impl TrayIcon {
@@ -137,11 +143,58 @@ impl TrayIcon {
                 hwnd,
                 internal_id,
                 menu: attrs.menu,
+                icon: attrs.icon,
+                animation_frame: None,
+                attention_icon: attrs.attention_icon,
+                overlay_icon: None,
+                attention: false,
             })
         }
     }
 
     pub fn set_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.icon = icon;
+        self.animation_frame = None;
+        self.update_icon()
+    }
+
+    pub fn set_animation_frame(&mut self, frame: Option<Icon>) -> crate::Result<()> {
+        self.animation_frame = frame;
+        self.update_icon()
+    }
+
+    pub fn set_attention_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.attention_icon = icon;
+        if self.attention {
+            self.update_icon()?;
+        }
+        Ok(())
+    }
+
+    /// The notification area has no overlays, so the overlay is drawn onto the icon.
+    pub fn set_overlay_icon(&mut self, icon: Option<Icon>) -> crate::Result<()> {
+        self.overlay_icon = icon;
+        self.update_icon()
+    }
+
+    /// The notification area has no attention state, so the icon itself is swapped.
+    pub fn set_attention(&mut self, attention: bool) -> crate::Result<()> {
+        self.attention = attention;
+        self.update_icon()
+    }
+
+    fn update_icon(&self) -> crate::Result<()> {
+        let icon = match &self.attention_icon {
+            Some(attention_icon) if self.attention => Some(attention_icon),
+            _ => self.animation_frame.as_ref().or(self.icon.as_ref()),
+        };
+        let icon = match (icon, &self.overlay_icon) {
+            (Some(icon), Some(overlay)) => {
+                Some(icon.with_overlay(overlay).unwrap_or_else(|| icon.clone()))
+            }
+            (icon, _) => icon.cloned(),
+        };
+
         unsafe {
             let mut nid = NOTIFYICONDATAW {
                 uFlags: NIF_ICON,
@@ -335,6 +388,10 @@ unsafe extern "system" fn tray_proc(
                 &userdata.icon.as_ref().map(|i| i.inner.as_raw_handle()),
                 &userdata.tooltip,
             );
+
+            TrayIconEvent::send(TrayIconEvent::Reregistered {
+                id: userdata.id.clone(),
+            });
         }
         WM_USER_SHOW_MENU_ON_LEFT_CLICK => {
             userdata.menu_on_left_click = wparam != 0;
@@ -501,6 +558,34 @@ unsafe extern "system" fn tray_timer_proc(hwnd: HWND, msg: u32, wparam: WPARAM,
     tray_proc(hwnd, msg, wparam, lparam as _);
 }
 
+thread_local! {
+    /// Callbacks of the timers started by [`run_after`] on this thread, by timer id.
+    static TIMERS: RefCell<HashMap<usize, Box<dyn FnOnce()>>> = RefCell::default();
+}
+
+/// Runs `f` from the message loop of the current thread after `delay`.
+pub(crate) fn run_after(delay: Duration, f: impl FnOnce() + 'static) {
+    unsafe extern "system" fn fire(_hwnd: HWND, _msg: u32, id: usize, _time: u32) {
+        KillTimer(ptr::null_mut(), id);
+        // the callback may start another timer, so it's removed before being called
+        if let Some(f) = TIMERS.with_borrow_mut(|timers| timers.remove(&id)) {
+            f();
+        }
+    }
+
+    let delay = delay.as_millis().min(u32::MAX as u128) as u32;
+    let id = unsafe { SetTimer(ptr::null_mut(), 0, delay, Some(fire)) };
+    if id != 0 {
+        TIMERS.with_borrow_mut(|timers| timers.insert(id, Box::new(f)));
+    }
+}
+
+/// The notification area is part of the taskbar, so it is always there.
+#[inline]
+pub fn host_status() -> crate::HostStatus {
+    crate::HostStatus::Available
+}
+
 #[inline]
 unsafe fn show_tray_menu(hwnd: HWND, menu: HMENU, x: i32, y: i32) {
     // bring the hidden window to the foreground so the pop up menu